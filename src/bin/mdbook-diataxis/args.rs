@@ -42,6 +42,9 @@ impl Args {
 
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
+    /// Check the Diátaxis structure of a book
+    Check(CheckCmd),
+
     /// Set up the diataxis framework
     Install(InstallCmd),
 
@@ -49,10 +52,21 @@ pub(crate) enum Command {
     Supports(SupportsCmd),
 }
 
+#[derive(Debug, Parser)]
+pub(crate) struct CheckCmd {
+    /// Book root directory (must contain `book.toml`)
+    #[arg(default_value = ".", value_name = "dir")]
+    pub(crate) book_root_dir: PathBuf,
+}
+
 #[derive(Debug, Parser)]
 pub(crate) struct SupportsCmd {
     /// The renderer to check
     pub(crate) renderer: String,
+
+    /// Book root directory (default: the nearest directory containing `book.toml`)
+    #[arg(value_name = "dir")]
+    pub(crate) book_root_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
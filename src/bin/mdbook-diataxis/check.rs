@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use mdbook::MDBook;
+use mdbook_diataxis::{DiataxisPreprocessor, Problem, Severity};
+
+use crate::args::CheckCmd;
+
+/// Checks the book and prints the problems found, returning whether it has no errors.
+pub(crate) fn check(cmd: CheckCmd) -> Result<bool> {
+    let CheckCmd { book_root_dir } = cmd;
+    let problems = check_book(&book_root_dir)?;
+    for problem in &problems {
+        let label = match problem.severity() {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        eprintln!("{label}: {problem}");
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity() == Severity::Error)
+        .count();
+    let warnings = problems.len() - errors;
+    eprintln!("Found {errors} error(s) and {warnings} warning(s)");
+    Ok(errors == 0)
+}
+
+fn check_book(book_root_dir: &Path) -> Result<Vec<Problem>> {
+    let book = MDBook::load(book_root_dir)
+        .with_context(|| anyhow!("cannot load book in {}", book_root_dir.display()))?;
+    DiataxisPreprocessor::new().check(&book.root, &book.config, book.book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{elements_are, eq, starts_with};
+    use indoc::indoc;
+
+    use crate::install::write_file;
+
+    #[googletest::test]
+    fn problems() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        write_file(
+            root.join("book.toml"),
+            indoc! {r#"
+                [book]
+                title = "TITLE"
+
+                [preprocessor.diataxis.compass]
+                order = ["tutorials", "how-to-guides", "reference"]
+
+                [preprocessor.diataxis.compass.tutorials]
+                link = "tutorials/README.md"
+
+                [preprocessor.diataxis.compass.reference]
+                link = "reference/README.md"
+            "#},
+        )
+        .unwrap();
+        write_file(
+            root.join("src/SUMMARY.md"),
+            indoc! {"
+                # Summary
+
+                [Introduction](README.md)
+
+                - [Tutorials](tutorials/README.md)
+                    - [First steps](tutorials/first-steps.md)
+                    - [Misplaced](tutorials/misplaced.md)
+                - [Reference](reference/README.md)
+            "},
+        )
+        .unwrap();
+        write_file(
+            root.join("src/README.md"),
+            "# Introduction\n\n{{#diataxis compas}}\n",
+        )
+        .unwrap();
+        write_file(root.join("src/tutorials/README.md"), "# Tutorials\n").unwrap();
+        write_file(root.join("src/tutorials/first-steps.md"), "# First steps\n").unwrap();
+        write_file(
+            root.join("src/tutorials/misplaced.md"),
+            "{{#diataxis kind reference}}\n# Misplaced\n",
+        )
+        .unwrap();
+        write_file(root.join("src/reference/README.md"), "# Reference\n").unwrap();
+
+        let problems = check_book(root)
+            .unwrap()
+            .into_iter()
+            .map(|problem| format!("{:?}: {problem}", problem.severity()))
+            .collect::<Vec<_>>();
+        expect_that!(
+            problems,
+            elements_are![
+                starts_with("Error: README.md:3:1: malformed `{{#diataxis ...}}` expression"),
+                eq(
+                    "Error: book.toml: compass link for `how-to-guides` points to `how-to/index.html`, which is not a page in the book"
+                ),
+                starts_with("Warning: README.md: page has no kind"),
+                starts_with(
+                    "Error: tutorials/misplaced.md: page has kind `reference` but is in the `tutorials` section"
+                ),
+                eq("Warning: book.toml: no pages belong to the `how-to-guides` section"),
+            ]
+        );
+    }
+
+    #[googletest::test]
+    fn unclassified_reported_once() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        write_file(
+            root.join("book.toml"),
+            indoc! {r#"
+                [book]
+                title = "TITLE"
+
+                [preprocessor.diataxis.compass]
+                order = ["tutorials"]
+
+                [preprocessor.diataxis.compass.tutorials]
+                link = "tutorials/README.md"
+
+                [preprocessor.diataxis.kinds]
+                unclassified = "warn"
+            "#},
+        )
+        .unwrap();
+        write_file(
+            root.join("src/SUMMARY.md"),
+            indoc! {"
+                # Summary
+
+                [Introduction](README.md)
+
+                - [Tutorials](tutorials/README.md)
+                    - [First steps](tutorials/first-steps.md)
+            "},
+        )
+        .unwrap();
+        write_file(root.join("src/README.md"), "# Introduction\n").unwrap();
+        write_file(root.join("src/tutorials/first-steps.md"), "# First steps\n").unwrap();
+        write_file(
+            root.join("src/tutorials/README.md"),
+            "{{#diataxis kind tutorial}}\n# Tutorials\n",
+        )
+        .unwrap();
+
+        let problems = check_book(root)
+            .unwrap()
+            .into_iter()
+            .map(|problem| format!("{:?}: {problem}", problem.severity()))
+            .collect::<Vec<_>>();
+        expect_that!(
+            problems,
+            elements_are![
+                starts_with("Warning: README.md: page has no kind"),
+                starts_with("Warning: tutorials/first-steps.md: page has no kind"),
+            ]
+        );
+    }
+}
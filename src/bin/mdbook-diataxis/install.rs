@@ -132,6 +132,94 @@ fn write_css(cmd: &InstallConfig) -> Result<()> {
             .quote-grid > blockquote {
                 margin: 0;
             }
+
+            .quote-grid > blockquote.diataxis-card-active {
+                border-left-width: 0.8ex;
+                box-shadow: 0 0 0 0.2ex var(--quote-border);
+            }
+
+            .diataxis-axes {
+                display: grid;
+                grid-template-areas:
+                    \". action .\"
+                    \"acquisition grid application\"
+                    \". cognition .\";
+                grid-template-columns: auto 1fr auto;
+                align-items: center;
+                gap: 1ex;
+                margin: 3.55ex 0;
+            }
+
+            .diataxis-axes > .diataxis-axes-grid {
+                grid-area: grid;
+                grid-template-columns: repeat(2, 1fr);
+                margin: 0;
+            }
+
+            .diataxis-axes-grid > .diataxis-card-placeholder {
+                min-height: 10ex;
+            }
+
+            .diataxis-axis-label {
+                justify-self: center;
+                font-size: 0.85em;
+                font-variant: small-caps;
+                opacity: 0.8;
+            }
+
+            .diataxis-axis-action {
+                grid-area: action;
+            }
+
+            .diataxis-axis-cognition {
+                grid-area: cognition;
+            }
+
+            .diataxis-axis-acquisition {
+                grid-area: acquisition;
+                writing-mode: vertical-rl;
+                transform: rotate(180deg);
+            }
+
+            .diataxis-axis-application {
+                grid-area: application;
+                writing-mode: vertical-rl;
+            }
+
+            .diataxis-svg-compass {
+                margin: 3.55ex 0;
+            }
+
+            .diataxis-svg-compass > svg {
+                width: 100%;
+                height: auto;
+            }
+
+            .diataxis-badge {
+                display: inline-block;
+                padding: 0.3ex 1ex;
+                border-left: 0.6ex solid var(--quote-border);
+                border-radius: 0.5ex;
+                background-color: var(--quote-bg);
+                font-size: 0.85em;
+                font-weight: bold;
+            }
+
+            .diataxis-badge-tutorial {
+                border-left-color: #4caf50;
+            }
+
+            .diataxis-badge-how-to-guide {
+                border-left-color: #2196f3;
+            }
+
+            .diataxis-badge-explanation {
+                border-left-color: #ff9800;
+            }
+
+            .diataxis-badge-reference {
+                border-left-color: #9c27b0;
+            }
         "},
     )?;
     Ok(())
@@ -1,7 +1,10 @@
 mod args;
+mod check;
 mod install;
 
+use std::env;
 use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 use clap::Parser;
@@ -10,26 +13,67 @@ use mdbook::preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext};
 use mdbook_diataxis::DiataxisPreprocessor;
 use semver::{Version, VersionReq};
 
-use crate::args::{Args, Command, InstallCmd, SupportsCmd};
+use crate::args::{Args, CheckCmd, Command, InstallCmd, SupportsCmd};
 
 fn main() -> ExitCode {
     let args = Args::parse();
     match args.command {
+        Some(Command::Check(cmd)) => run_check_command(cmd),
         Some(Command::Supports(cmd)) => run_supports_command(cmd),
         Some(Command::Install(cmd)) => run_install_command(cmd),
         None => preprocess(io::stdin()),
     }
 }
 
+fn run_check_command(cmd: CheckCmd) -> ExitCode {
+    match check::check(cmd) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
 fn run_supports_command(cmd: SupportsCmd) -> ExitCode {
-    let SupportsCmd { renderer } = cmd;
-    if DiataxisPreprocessor::new().supports_renderer(&renderer) {
+    let SupportsCmd {
+        renderer,
+        book_root_dir,
+    } = cmd;
+    let preprocessor = match supports_preprocessor(book_root_dir) {
+        Ok(preprocessor) => preprocessor,
+        Err(err) => {
+            eprintln!("{err:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if preprocessor.supports_renderer(&renderer) {
         ExitCode::SUCCESS
     } else {
         ExitCode::FAILURE
     }
 }
 
+/// Creates a preprocessor configured by the `book.toml` in `book_root_dir`. mdBook does not say
+/// where the book is, so by default this is the nearest directory containing a `book.toml`,
+/// starting from the current directory, which is where mdBook is usually run from.
+fn supports_preprocessor(book_root_dir: Option<PathBuf>) -> Result<DiataxisPreprocessor> {
+    let book_root_dir = match book_root_dir {
+        Some(book_root_dir) => Some(book_root_dir),
+        None => env::current_dir()?
+            .ancestors()
+            .find(|dir| dir.join("book.toml").is_file())
+            .map(Path::to_owned),
+    };
+    let Some(book_root_dir) = book_root_dir else {
+        return Ok(DiataxisPreprocessor::new());
+    };
+    let mut config = mdbook::Config::from_disk(book_root_dir.join("book.toml"))?;
+    config.update_from_env();
+    DiataxisPreprocessor::with_book_config(&config)
+}
+
 fn run_install_command(cmd: InstallCmd) -> ExitCode {
     match install::install(cmd) {
         Ok(_) => ExitCode::SUCCESS,
@@ -74,3 +118,32 @@ fn check_version(preprocessor: &DiataxisPreprocessor, ctx: &PreprocessorContext)
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::eq;
+    use indoc::indoc;
+
+    use crate::install::write_file;
+
+    #[googletest::test]
+    fn supports_configured_renderers() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        write_file(
+            root.join("book.toml"),
+            indoc! {r#"
+                [preprocessor.diataxis.renderers]
+                supported = ["html"]
+            "#},
+        )
+        .unwrap();
+
+        let preprocessor = supports_preprocessor(Some(root.to_owned())).unwrap();
+        expect_that!(preprocessor.supports_renderer("html"), eq(true));
+        expect_that!(preprocessor.supports_renderer("epub"), eq(false));
+    }
+}
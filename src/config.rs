@@ -0,0 +1,1153 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use mdbook::book::Book;
+use mdbook::BookItem;
+use toml::value::Table;
+
+use crate::diagnostic::closest_match;
+use crate::directive::CompassLayout;
+use crate::link::Link;
+use crate::quadrant::Quadrant;
+use crate::template::Templates;
+use crate::translation::{self, primary_subtag, AxisLabels, SectionText};
+use crate::OutputFormat;
+
+/// The renderers for which directives are expanded.
+#[derive(Debug, Default)]
+pub(crate) struct RendererConfig {
+    /// The names of the supported renderers, or `None` if all renderers are supported.
+    supported: Option<Vec<String>>,
+
+    /// How directives are handled for renderers which are not supported.
+    unlisted: UnlistedRenderers,
+}
+
+impl RendererConfig {
+    /// Parses the `renderers` field, which is either a list of supported renderers or a table.
+    pub(crate) fn new(raw: &toml::Value) -> Result<Self> {
+        let parse_supported = |value: &toml::Value| -> Result<Vec<String>> {
+            value
+                .as_array()
+                .ok_or_else(|| anyhow!("`supported` field must be an array of strings"))?
+                .iter()
+                .map(|renderer| {
+                    renderer
+                        .as_str()
+                        .map(str::to_owned)
+                        .ok_or_else(|| anyhow!("renderer names must be strings"))
+                })
+                .collect()
+        };
+        // mdBook itself skips the preprocessor for renderers which are missing from a list, so
+        // directives are only kept here when the preprocessor is used as a library.
+        if raw.is_array() {
+            return Ok(Self {
+                supported: Some(parse_supported(raw)?),
+                unlisted: UnlistedRenderers::default(),
+            });
+        }
+
+        let table = raw
+            .as_table()
+            .ok_or_else(|| anyhow!("`renderers` field must be an array or a table"))?;
+        let supported = table.get("supported").map(parse_supported).transpose()?;
+        let unlisted = table
+            .get("unlisted")
+            .map(|unlisted| {
+                let unlisted = unlisted
+                    .as_str()
+                    .ok_or_else(|| anyhow!("`unlisted` field must be a string"))?;
+                UnlistedRenderers::from_name(unlisted).ok_or_else(|| {
+                    anyhow!(
+                        "unknown `unlisted` value `{unlisted}`, expected `keep`, `strip` or `text`"
+                    )
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self {
+            supported,
+            unlisted,
+        })
+    }
+
+    pub(crate) fn handling(&self, renderer: &str) -> DirectiveHandling {
+        let supported = self
+            .supported
+            .as_ref()
+            .is_none_or(|supported| supported.iter().any(|name| name == renderer));
+        if supported {
+            return DirectiveHandling::Expand(OutputFormat::for_renderer(renderer));
+        }
+        match self.unlisted {
+            UnlistedRenderers::Keep => DirectiveHandling::Keep,
+            UnlistedRenderers::Strip => DirectiveHandling::Strip,
+            UnlistedRenderers::Text => DirectiveHandling::Expand(OutputFormat::Text),
+        }
+    }
+}
+
+/// How to handle directives for renderers which are not listed as supported.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+enum UnlistedRenderers {
+    /// Leave directives as they are written.
+    #[default]
+    Keep,
+
+    /// Remove directives.
+    Strip,
+
+    /// Replace directives with plain text.
+    Text,
+}
+
+impl UnlistedRenderers {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(Self::Keep),
+            "strip" => Some(Self::Strip),
+            "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with the directives in a book for a given renderer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum DirectiveHandling {
+    Expand(OutputFormat),
+    Keep,
+    Strip,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Config<'cfg> {
+    /// Whether problems found in the book should fail the build.
+    pub(crate) strict: bool,
+    pub(crate) renderers: RendererConfig,
+
+    /// The paths of user-supplied templates, keyed by the name of the markup they replace.
+    pub(crate) templates: Vec<(&'static str, &'cfg str)>,
+    tutorials: SectionConfig<'cfg>,
+    how_to_guides: SectionConfig<'cfg>,
+    reference: SectionConfig<'cfg>,
+    explanation: SectionConfig<'cfg>,
+
+    /// Whether compass descriptions are rendered as inline markdown rather than plain text.
+    pub(crate) markdown_descriptions: bool,
+
+    /// The layout of compasses which do not choose their own.
+    pub(crate) layout: CompassLayout,
+
+    /// Cards shown in the compass in addition to the four quadrants, keyed by name.
+    extra_cards: Vec<(&'cfg str, SectionConfig<'cfg>)>,
+
+    /// The cards to show in the compass, in order, if not the default.
+    order: Option<Vec<CompassCard<'cfg>>>,
+
+    /// Whether a table of contents is added to section pages which have no content of their own.
+    pub(crate) auto_toc: bool,
+
+    /// The pages to which a compass is added automatically.
+    pub(crate) auto_compass: Option<AutoCompass>,
+
+    /// Whether landing pages are generated for quadrants whose pages are missing.
+    pub(crate) landing_pages: bool,
+
+    /// The patterns from which the kinds of pages are inferred.
+    pub(crate) kind_patterns: KindPatterns,
+
+    /// The language of the book, used to choose the default compass text.
+    language: Option<&'cfg str>,
+
+    /// The user's translations of the compass into the book's language.
+    translations: HashMap<Quadrant, SectionConfig<'cfg>>,
+}
+
+impl<'cfg> Config<'cfg> {
+    /// Reads the `[preprocessor.diataxis]` table of `book_config`.
+    pub(crate) fn from_book_config(book_config: &'cfg mdbook::Config) -> Result<Self> {
+        book_config
+            .get_preprocessor("diataxis")
+            .map(|raw| Self::new(raw, book_config.book.language.as_deref()))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    fn new(raw: &'cfg Table, language: Option<&'cfg str>) -> Result<Self> {
+        let section_overrides = |section| -> Result<SectionConfig<'_>> {
+            let overrides = raw
+                .get("compass")
+                .map(|compass_value| {
+                    compass_value
+                        .as_table()
+                        .ok_or_else(|| anyhow!("`compass` field must be a table"))
+                })
+                .transpose()?
+                .and_then(|compass_table| compass_table.get(section))
+                .map(|section_value| {
+                    section_value
+                        .as_table()
+                        .ok_or_else(|| anyhow!("`compass.{section}` field must be a table"))
+                })
+                .transpose()?
+                .map(|section_table| {
+                    SectionConfig::new_for_language(section_table, language)
+                        .with_context(|| anyhow!("cannot parse `compass.{section}` table"))
+                })
+                .transpose()?
+                .unwrap_or_default();
+            Ok(overrides)
+        };
+        let markdown_descriptions = match raw
+            .get("compass")
+            .and_then(|compass| compass.get("descriptions"))
+        {
+            None => false,
+            Some(descriptions) => match descriptions.as_str() {
+                Some("text") => false,
+                Some("markdown") => true,
+                _ => {
+                    return Err(anyhow!(
+                        "`compass.descriptions` field must be either \"text\" or \"markdown\""
+                    ));
+                }
+            },
+        };
+        let layout = raw
+            .get("compass")
+            .and_then(|compass| compass.get("layout"))
+            .map(|layout| {
+                let layout = layout
+                    .as_str()
+                    .ok_or_else(|| anyhow!("`compass.layout` field must be a string"))?;
+                CompassLayout::from_name(layout).ok_or_else(|| {
+                    anyhow!("unknown compass layout `{layout}`, expected `grid`, `axes` or `svg`")
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let extra_cards = raw
+            .get("compass")
+            .and_then(|compass| compass.get("cards"))
+            .map(|cards| Self::extra_cards(cards, language))
+            .transpose()?
+            .unwrap_or_default();
+        let order = raw
+            .get("compass")
+            .and_then(|compass| compass.get("order"))
+            .map(|order| Self::order(order, &extra_cards))
+            .transpose()?;
+        let auto_toc = raw
+            .get("auto-toc")
+            .map(|auto_toc| {
+                auto_toc
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("`auto-toc` field must be a boolean"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let auto_compass = raw
+            .get("auto-compass")
+            .map(AutoCompass::new)
+            .transpose()
+            .context("cannot parse `auto-compass` field")?;
+        let landing_pages = raw
+            .get("landing-pages")
+            .map(|landing_pages| {
+                landing_pages
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("`landing-pages` field must be a boolean"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let kind_patterns = raw
+            .get("kinds")
+            .map(KindPatterns::new)
+            .transpose()
+            .context("cannot parse `kinds` field")?
+            .unwrap_or_default();
+        let translations = match language {
+            Some(language) => Self::translations(raw, language)?,
+            None => HashMap::new(),
+        };
+        let strict = raw
+            .get("strict")
+            .map(|strict| {
+                strict
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("`strict` field must be a boolean"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let renderers = raw
+            .get("renderers")
+            .map(RendererConfig::new)
+            .transpose()
+            .context("cannot parse `renderers` field")?
+            .unwrap_or_default();
+        let templates = raw
+            .get("templates")
+            .map(|templates| {
+                templates
+                    .as_table()
+                    .ok_or_else(|| anyhow!("`templates` field must be a table"))?
+                    .iter()
+                    .map(|(name, path)| {
+                        let name = Templates::NAMES
+                            .into_iter()
+                            .find(|known| known == name)
+                            .ok_or_else(|| {
+                                anyhow!(
+                                    "unknown template `{name}`, expected one of: {}",
+                                    Templates::NAMES.join(", ")
+                                )
+                            })?;
+                        let path = path
+                            .as_str()
+                            .ok_or_else(|| anyhow!("`templates.{name}` field must be a string"))?;
+                        Ok((name, path))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let tutorials = section_overrides("tutorials")?;
+        let how_to_guides = section_overrides("how-to-guides")?;
+        let explanation = section_overrides("explanation")?;
+        let reference = section_overrides("reference")?;
+        Ok(Self {
+            strict,
+            renderers,
+            templates,
+            tutorials,
+            how_to_guides,
+            explanation,
+            reference,
+            markdown_descriptions,
+            layout,
+            extra_cards,
+            order,
+            auto_toc,
+            auto_compass,
+            landing_pages,
+            kind_patterns,
+            language,
+            translations,
+        })
+    }
+
+    /// Reads the extra cards from the `compass.cards` table.
+    fn extra_cards(
+        cards: &'cfg toml::Value,
+        language: Option<&str>,
+    ) -> Result<Vec<(&'cfg str, SectionConfig<'cfg>)>> {
+        cards
+            .as_table()
+            .ok_or_else(|| anyhow!("`compass.cards` field must be a table"))?
+            .iter()
+            .map(|(name, card)| {
+                if Quadrant::ALL
+                    .iter()
+                    .any(|quadrant| quadrant.compass_section() == name)
+                {
+                    return Err(anyhow!(
+                        "compass card `{name}` has the same name as a quadrant"
+                    ));
+                }
+                let card = card
+                    .as_table()
+                    .ok_or_else(|| anyhow!("`compass.cards.{name}` field must be a table"))
+                    .and_then(|card| SectionConfig::new_for_language(card, language))
+                    .with_context(|| anyhow!("cannot parse `compass.cards.{name}` table"))?;
+                if card.title_override.is_none() {
+                    return Err(anyhow!("compass card `{name}` must have a `title`"));
+                }
+                if card.link_override.is_none() && card.chapter_name.is_none() {
+                    return Err(anyhow!(
+                        "compass card `{name}` must have either a `link` or a `chapter`"
+                    ));
+                }
+                Ok((name.as_str(), card))
+            })
+            .collect()
+    }
+
+    /// Reads the order of the compass's cards from the `compass.order` list.
+    fn order(
+        order: &'cfg toml::Value,
+        extra_cards: &[(&'cfg str, SectionConfig<'cfg>)],
+    ) -> Result<Vec<CompassCard<'cfg>>> {
+        let known = Quadrant::ALL
+            .into_iter()
+            .map(CompassCard::Quadrant)
+            .chain(extra_cards.iter().map(|(name, _)| CompassCard::Extra(name)))
+            .map(|card| card.name())
+            .collect::<Vec<_>>();
+        let mut cards = vec![];
+        for name in order
+            .as_array()
+            .ok_or_else(|| anyhow!("`compass.order` field must be an array"))?
+        {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("`compass.order` entries must be strings"))?;
+            let card = Quadrant::ALL
+                .into_iter()
+                .find(|quadrant| quadrant.compass_section() == name)
+                .map(CompassCard::Quadrant)
+                .or_else(|| {
+                    extra_cards
+                        .iter()
+                        .find(|(card_name, _)| *card_name == name)
+                        .map(|(card_name, _)| CompassCard::Extra(card_name))
+                });
+            let Some(card) = card else {
+                let help = closest_match(name, known.iter().copied())
+                    .map(|suggestion| format!(", did you mean `{suggestion}`?"))
+                    .unwrap_or_default();
+                return Err(anyhow!(
+                    "unknown compass card `{name}` in `compass.order`{help}"
+                ));
+            };
+            if cards.contains(&card) {
+                return Err(anyhow!(
+                    "compass card `{name}` appears more than once in `compass.order`"
+                ));
+            }
+            cards.push(card);
+        }
+        Ok(cards)
+    }
+
+    /// Returns the cards to show in the compass, in order.
+    ///
+    /// By default, these are the four quadrants followed by any extra cards.
+    pub(crate) fn compass_cards(&self) -> Vec<CompassCard<'cfg>> {
+        match &self.order {
+            Some(order) => order.clone(),
+            None => Quadrant::ALL
+                .into_iter()
+                .map(CompassCard::Quadrant)
+                .chain(
+                    self.extra_cards
+                        .iter()
+                        .map(|(name, _)| CompassCard::Extra(name)),
+                )
+                .collect(),
+        }
+    }
+
+    fn extra_card(&self, name: &str) -> &SectionConfig<'cfg> {
+        self.extra_cards
+            .iter()
+            .find_map(|(card_name, card)| (*card_name == name).then_some(card))
+            .expect("internal error: unknown compass card")
+    }
+
+    pub(crate) fn card_title(&self, card: CompassCard) -> &str {
+        match card {
+            CompassCard::Quadrant(Quadrant::Tutorial) => self.tutorials_title(),
+            CompassCard::Quadrant(Quadrant::HowToGuide) => self.how_to_guides_title(),
+            CompassCard::Quadrant(Quadrant::Explanation) => self.explanation_title(),
+            CompassCard::Quadrant(Quadrant::Reference) => self.reference_title(),
+            CompassCard::Extra(name) => self
+                .extra_card(name)
+                .title_override
+                .expect("internal error: compass card has no title"),
+        }
+    }
+
+    pub(crate) fn card_description(&self, card: CompassCard) -> &str {
+        match card {
+            CompassCard::Quadrant(Quadrant::Tutorial) => self.tutorials_description(),
+            CompassCard::Quadrant(Quadrant::HowToGuide) => self.how_to_guides_description(),
+            CompassCard::Quadrant(Quadrant::Explanation) => self.explanation_description(),
+            CompassCard::Quadrant(Quadrant::Reference) => self.reference_description(),
+            CompassCard::Extra(name) => self
+                .extra_card(name)
+                .description_override
+                .unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn card_link(&self, card: CompassCard) -> Link {
+        match card {
+            CompassCard::Quadrant(Quadrant::Tutorial) => self.tutorials_link(),
+            CompassCard::Quadrant(Quadrant::HowToGuide) => self.how_to_guides_link(),
+            CompassCard::Quadrant(Quadrant::Explanation) => self.explanation_link(),
+            CompassCard::Quadrant(Quadrant::Reference) => self.reference_link(),
+            CompassCard::Extra(name) => self
+                .extra_card(name)
+                .link_override
+                .clone()
+                .expect("internal error: compass card link is unresolved"),
+        }
+    }
+
+    /// Reads the user's translations into `language` from the `translations` table.
+    ///
+    /// A translation for the exact language tag, such as `fr-CA`, is preferred to one for its
+    /// primary subtag, such as `fr`.
+    fn translations(
+        raw: &'cfg Table,
+        language: &str,
+    ) -> Result<HashMap<Quadrant, SectionConfig<'cfg>>> {
+        let Some(translations) = raw.get("translations") else {
+            return Ok(HashMap::new());
+        };
+        let translations = translations
+            .as_table()
+            .ok_or_else(|| anyhow!("`translations` field must be a table"))?;
+        let Some((language, translation)) = [language, primary_subtag(language)]
+            .into_iter()
+            .find_map(|language| Some((language, translations.get(language)?)))
+        else {
+            return Ok(HashMap::new());
+        };
+        let translation = translation
+            .as_table()
+            .ok_or_else(|| anyhow!("`translations.{language}` field must be a table"))?;
+        translation
+            .iter()
+            .map(|(section, section_value)| {
+                let quadrant = Quadrant::ALL
+                    .into_iter()
+                    .find(|quadrant| quadrant.compass_section() == section)
+                    .ok_or_else(|| {
+                        anyhow!("unknown compass section `{section}` in `translations.{language}`")
+                    })?;
+                let section_config = section_value
+                    .as_table()
+                    .ok_or_else(|| {
+                        anyhow!("`translations.{language}.{section}` field must be a table")
+                    })
+                    .and_then(SectionConfig::new)
+                    .with_context(|| {
+                        anyhow!("cannot parse `translations.{language}.{section}` table")
+                    })?;
+                if section_config.link_override.is_some() || section_config.chapter_name.is_some() {
+                    return Err(anyhow!(
+                        "`translations.{language}.{section}` can only set `title` and `description`"
+                    ));
+                }
+                Ok((quadrant, section_config))
+            })
+            .collect()
+    }
+
+    /// Returns the title of `quadrant`'s compass section when it is not overridden.
+    fn default_title(&self, quadrant: Quadrant) -> &str {
+        self.translations
+            .get(&quadrant)
+            .and_then(|translation| translation.title_override)
+            .unwrap_or_else(|| self.builtin_text(quadrant).title)
+    }
+
+    /// Returns the description of `quadrant`'s compass section when it is not overridden.
+    fn default_description(&self, quadrant: Quadrant) -> &str {
+        self.translations
+            .get(&quadrant)
+            .and_then(|translation| translation.description_override)
+            .unwrap_or_else(|| self.builtin_text(quadrant).description)
+    }
+
+    /// Returns the labels of the axes of the compass in the book's language, or in English if they
+    /// have not been translated.
+    pub(crate) fn axis_labels(&self) -> AxisLabels {
+        self.language
+            .and_then(translation::builtin_axis_labels)
+            .or_else(|| translation::builtin_axis_labels("en"))
+            .expect("internal error: missing English axis labels")
+    }
+
+    /// Returns the built-in text of `quadrant`'s compass section in the book's language, or in
+    /// English if it has not been translated.
+    fn builtin_text(&self, quadrant: Quadrant) -> SectionText {
+        self.language
+            .and_then(|language| translation::builtin(language, quadrant))
+            .or_else(|| translation::builtin("en", quadrant))
+            .expect("internal error: missing English compass text")
+    }
+
+    /// Sets the links of shown sections configured with a `chapter` name to point to that chapter.
+    pub(crate) fn resolve_chapter_links(&mut self, book: &Book) -> Result<()> {
+        let shown_cards = self.compass_cards();
+        let quadrant_sections = [
+            (Quadrant::Tutorial, &mut self.tutorials),
+            (Quadrant::HowToGuide, &mut self.how_to_guides),
+            (Quadrant::Explanation, &mut self.explanation),
+            (Quadrant::Reference, &mut self.reference),
+        ]
+        .into_iter()
+        .filter(|(quadrant, _)| shown_cards.contains(&CompassCard::Quadrant(*quadrant)))
+        .map(|(quadrant, card)| (quadrant.compass_section().to_owned(), card));
+        let extra_sections = self
+            .extra_cards
+            .iter_mut()
+            .filter(|(name, _)| shown_cards.contains(&CompassCard::Extra(name)))
+            .map(|(name, card)| (format!("cards.{name}"), card));
+        for (section, section_config) in quadrant_sections.chain(extra_sections) {
+            let Some(chapter_name) = section_config.chapter_name else {
+                continue;
+            };
+            let mut matching_chapters = book.iter().filter_map(|item| match item {
+                BookItem::Chapter(chapter) if chapter.name == chapter_name => Some(chapter),
+                _ => None,
+            });
+            let chapter = matching_chapters.next().ok_or_else(|| {
+                anyhow!("cannot find chapter `{chapter_name}` named in `compass.{section}.chapter`")
+            })?;
+            if matching_chapters.next().is_some() {
+                return Err(anyhow!(
+                    "more than one chapter is called `{chapter_name}`, as named in `compass.{section}.chapter`"
+                ));
+            }
+            let path = chapter.path.as_deref().ok_or_else(|| {
+                anyhow!("chapter `{chapter_name}` named in `compass.{section}.chapter` is a draft")
+            })?;
+            section_config.link_override = Some(Link::to_chapter(path));
+        }
+        Ok(())
+    }
+
+    fn tutorials_title(&self) -> &str {
+        self.tutorials
+            .title_override
+            .unwrap_or_else(|| self.default_title(Quadrant::Tutorial))
+    }
+
+    fn tutorials_description(&self) -> &str {
+        self.tutorials
+            .description_override
+            .unwrap_or_else(|| self.default_description(Quadrant::Tutorial))
+    }
+
+    pub(crate) fn tutorials_link(&self) -> Link {
+        self.tutorials
+            .link_override
+            .clone()
+            .unwrap_or_else(|| Link::internal("./tutorials/index.md"))
+    }
+
+    fn how_to_guides_title(&self) -> &str {
+        self.how_to_guides
+            .title_override
+            .unwrap_or_else(|| self.default_title(Quadrant::HowToGuide))
+    }
+
+    fn how_to_guides_description(&self) -> &str {
+        self.how_to_guides
+            .description_override
+            .unwrap_or_else(|| self.default_description(Quadrant::HowToGuide))
+    }
+
+    pub(crate) fn how_to_guides_link(&self) -> Link {
+        self.how_to_guides
+            .link_override
+            .clone()
+            .unwrap_or_else(|| Link::internal("./how-to/index.md"))
+    }
+
+    fn explanation_title(&self) -> &str {
+        self.explanation
+            .title_override
+            .unwrap_or_else(|| self.default_title(Quadrant::Explanation))
+    }
+
+    fn explanation_description(&self) -> &str {
+        self.explanation
+            .description_override
+            .unwrap_or_else(|| self.default_description(Quadrant::Explanation))
+    }
+
+    pub(crate) fn explanation_link(&self) -> Link {
+        self.explanation
+            .link_override
+            .clone()
+            .unwrap_or_else(|| Link::internal("./explanations/index.md"))
+    }
+
+    fn reference_title(&self) -> &str {
+        self.reference
+            .title_override
+            .unwrap_or_else(|| self.default_title(Quadrant::Reference))
+    }
+
+    fn reference_description(&self) -> &str {
+        self.reference
+            .description_override
+            .unwrap_or_else(|| self.default_description(Quadrant::Reference))
+    }
+
+    pub(crate) fn reference_link(&self) -> Link {
+        self.reference
+            .link_override
+            .clone()
+            .unwrap_or_else(|| Link::internal("./reference-materials/index.md"))
+    }
+}
+
+/// Patterns which assign kinds to pages by their paths.
+#[derive(Debug, Default)]
+pub(crate) struct KindPatterns {
+    /// The patterns for each kind, matched against the source paths of pages.
+    patterns: Vec<(Quadrant, GlobSet)>,
+
+    /// Whether to report pages whose kind is neither declared nor inferred.
+    pub(crate) report_unclassified: bool,
+}
+
+impl KindPatterns {
+    pub(crate) fn new(raw: &toml::Value) -> Result<Self> {
+        let table = raw
+            .as_table()
+            .ok_or_else(|| anyhow!("`kinds` field must be a table"))?;
+        let mut patterns = vec![];
+        let mut report_unclassified = false;
+        for (key, value) in table {
+            if key == "unclassified" {
+                report_unclassified = match value.as_str() {
+                    Some("ignore") => false,
+                    Some("warn") => true,
+                    _ => {
+                        return Err(anyhow!(
+                            "`unclassified` field must be either \"ignore\" or \"warn\""
+                        ));
+                    }
+                };
+                continue;
+            }
+            let Some(quadrant) = Quadrant::from_name(key) else {
+                let known = Quadrant::ALL.map(|quadrant| quadrant.name());
+                let suggestion = closest_match(key, known)
+                    .map(|suggestion| format!(", did you mean `{suggestion}`?"))
+                    .unwrap_or_default();
+                return Err(anyhow!("unknown kind `{key}`{suggestion}"));
+            };
+            patterns.push((quadrant, parse_globs(value, key)?));
+        }
+        patterns.sort_by_key(|(quadrant, _)| {
+            Quadrant::ALL
+                .iter()
+                .position(|known| known == quadrant)
+                .expect("internal error: unknown quadrant")
+        });
+        Ok(Self {
+            patterns,
+            report_unclassified,
+        })
+    }
+
+    /// Returns the kinds with a pattern which matches the source path `path`.
+    pub(crate) fn matching(&self, path: &Path) -> Vec<Quadrant> {
+        self.patterns
+            .iter()
+            .filter(|(_, globs)| globs.is_match(path))
+            .map(|(quadrant, _)| *quadrant)
+            .collect()
+    }
+}
+
+/// Where to add a compass to pages automatically.
+#[derive(Debug)]
+pub(crate) struct AutoCompass {
+    /// The pages to add a compass to, matched against their source paths.
+    pub(crate) pages: GlobSet,
+    pub(crate) position: AutoCompassPosition,
+}
+
+impl AutoCompass {
+    fn new(raw: &toml::Value) -> Result<Self> {
+        let table = raw
+            .as_table()
+            .ok_or_else(|| anyhow!("`auto-compass` field must be a table"))?;
+        let pages = table
+            .get("pages")
+            .ok_or_else(|| anyhow!("`pages` field is required"))
+            .and_then(|pages| parse_globs(pages, "pages"))?;
+        let position = match table.get("position").map(|position| position.as_str()) {
+            None | Some(Some("top")) => AutoCompassPosition::Top,
+            Some(Some("bottom")) => AutoCompassPosition::Bottom,
+            _ => {
+                return Err(anyhow!(
+                    "`position` field must be either \"top\" or \"bottom\""
+                ))
+            }
+        };
+        Ok(Self { pages, position })
+    }
+}
+
+/// Where on a page an automatic compass is added.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum AutoCompassPosition {
+    /// After the page's leading heading, if it has one after any blank or directive-only lines,
+    /// or otherwise at the start of the page.
+    Top,
+
+    /// At the end of the page.
+    Bottom,
+}
+
+/// Parses a list of glob patterns, such as `["README.md", "*/index.md"]`, in the field called
+/// `field`.
+///
+/// A `*` does not match across directories, but a `**` does.
+fn parse_globs(raw: &toml::Value, field: &str) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in raw
+        .as_array()
+        .ok_or_else(|| anyhow!("`{field}` field must be an array of strings"))?
+    {
+        let pattern = pattern
+            .as_str()
+            .ok_or_else(|| anyhow!("`{field}` entries must be strings"))?;
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| anyhow!("cannot parse pattern `{pattern}` in `{field}`"))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .with_context(|| anyhow!("cannot build patterns in `{field}`"))
+}
+
+/// A card which can be shown in the compass.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum CompassCard<'cfg> {
+    Quadrant(Quadrant),
+
+    /// A card configured in the `compass.cards` table.
+    Extra(&'cfg str),
+}
+
+impl<'cfg> CompassCard<'cfg> {
+    /// The name of this card in `book.toml`.
+    pub(crate) fn name(&self) -> &'cfg str {
+        match self {
+            Self::Quadrant(quadrant) => quadrant.compass_section(),
+            Self::Extra(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SectionConfig<'cfg> {
+    title_override: Option<&'cfg str>,
+    description_override: Option<&'cfg str>,
+    link_override: Option<Link>,
+
+    /// The name of the chapter to link to, as written in `SUMMARY.md`.
+    chapter_name: Option<&'cfg str>,
+}
+
+impl<'cfg> SectionConfig<'cfg> {
+    /// Parses a compass section's table, applying the overrides in its `lang.<language>` table.
+    ///
+    /// Overrides for the exact language tag, such as `fr-CA`, are preferred to those for its
+    /// primary subtag, such as `fr`.
+    fn new_for_language(config_table: &'cfg Table, language: Option<&str>) -> Result<Self> {
+        let mut section_config = Self::new(config_table)?;
+        let (Some(language), Some(lang)) = (language, config_table.get("lang")) else {
+            return Ok(section_config);
+        };
+        let lang = lang
+            .as_table()
+            .ok_or_else(|| anyhow!("`lang` field must be a table"))?;
+        let Some((language, overrides)) = [language, primary_subtag(language)]
+            .into_iter()
+            .find_map(|language| Some((language, lang.get(language)?)))
+        else {
+            return Ok(section_config);
+        };
+        let overrides = overrides
+            .as_table()
+            .ok_or_else(|| anyhow!("`lang.{language}` field must be a table"))
+            .and_then(Self::new)
+            .with_context(|| anyhow!("cannot parse `lang.{language}` table"))?;
+        section_config.apply(overrides);
+        Ok(section_config)
+    }
+
+    /// Replaces the fields of this section's config with those set in `overrides`.
+    fn apply(&mut self, overrides: Self) {
+        let Self {
+            title_override,
+            description_override,
+            link_override,
+            chapter_name,
+        } = overrides;
+        if title_override.is_some() {
+            self.title_override = title_override;
+        }
+        if description_override.is_some() {
+            self.description_override = description_override;
+        }
+        if link_override.is_some() || chapter_name.is_some() {
+            self.link_override = link_override;
+            self.chapter_name = chapter_name;
+        }
+    }
+
+    fn new(config_table: &'cfg Table) -> Result<Self> {
+        let title_override = config_table
+            .get("title")
+            .map(|title| {
+                title
+                    .as_str()
+                    .ok_or_else(|| anyhow!("`title` field must be a string"))
+            })
+            .transpose()?;
+        let description_override = config_table
+            .get("description")
+            .map(|desc| {
+                desc.as_str()
+                    .ok_or_else(|| anyhow!("`description` field must be a string"))
+            })
+            .transpose()?;
+        let link_override = config_table
+            .get("link")
+            .map(|file| {
+                file.as_str()
+                    .ok_or_else(|| anyhow!("`link` field must be a string"))
+            })
+            .transpose()?
+            .map(Link::parse);
+        let chapter_name = config_table
+            .get("chapter")
+            .map(|chapter| {
+                chapter
+                    .as_str()
+                    .ok_or_else(|| anyhow!("`chapter` field must be a string"))
+            })
+            .transpose()?;
+        if link_override.is_some() && chapter_name.is_some() {
+            return Err(anyhow!("`link` and `chapter` fields cannot both be set"));
+        }
+        Ok(Self {
+            title_override,
+            description_override,
+            link_override,
+            chapter_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{contains_substring, eq, not};
+    use indoc::indoc;
+    use insta::assert_snapshot;
+    use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+
+    use crate::DiataxisPreprocessor;
+
+    mod renderers {
+        use super::*;
+
+        #[googletest::test]
+        fn supported() {
+            let all = DiataxisPreprocessor::new();
+            expect_that!(all.supports_renderer("html"), eq(true));
+            expect_that!(all.supports_renderer("epub"), eq(true));
+
+            let listed = indoc! {r#"
+                [preprocessor.diataxis]
+                renderers = ["html", "markdown"]
+            "#}
+            .parse::<mdbook::Config>()
+            .unwrap();
+            let listed = DiataxisPreprocessor::with_book_config(&listed).unwrap();
+            expect_that!(listed.supports_renderer("html"), eq(true));
+            expect_that!(listed.supports_renderer("markdown"), eq(true));
+            expect_that!(listed.supports_renderer("epub"), eq(false));
+
+            let stripped = indoc! {r#"
+                [preprocessor.diataxis.renderers]
+                supported = ["html"]
+                unlisted = "strip"
+            "#}
+            .parse::<mdbook::Config>()
+            .unwrap();
+            let stripped = DiataxisPreprocessor::with_book_config(&stripped).unwrap();
+            expect_that!(stripped.supports_renderer("epub"), eq(true));
+        }
+
+        #[googletest::test]
+        fn unlisted_kept() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "renderers": ["html"]
+                            }
+                        }
+                    },
+                    "renderer": "epub",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis kind reference}}\n\n{{#diataxis compass}}\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Chapter 1.1",
+                                    "content": "",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/section_1.md",
+                                    "source_path": "chapter_1/section_1.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq("# Chapter 1\n{{#diataxis kind reference}}\n\n{{#diataxis compass}}\n{{#diataxis table-of-contents}}")
+            );
+        }
+
+        #[googletest::test]
+        fn unlisted_stripped() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "renderers": { "supported": ["html"], "unlisted": "strip" }
+                            }
+                        }
+                    },
+                    "renderer": "epub",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis kind reference}}\n\n{{#diataxis compass}}\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Chapter 1.1",
+                                    "content": "",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/section_1.md",
+                                    "source_path": "chapter_1/section_1.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(chapter.content, eq("# Chapter 1\n\n\n\n"));
+        }
+
+        #[googletest::test]
+        fn unlisted_as_text() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "renderers": { "supported": ["html"], "unlisted": "text" }
+                            }
+                        }
+                    },
+                    "renderer": "epub",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis kind reference}}\n\n{{#diataxis compass}}\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Chapter 1.1",
+                                    "content": "",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/section_1.md",
+                                    "source_path": "chapter_1/section_1.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(chapter.content, not(contains_substring("](")));
+            assert_snapshot!(chapter.content);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+/// A problem found in the book while preprocessing it.
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    /// The path of the file which contains the problem, relative to the book's source directory
+    /// for chapters or to the book's root directory for `book.toml`.
+    pub(crate) path: PathBuf,
+
+    /// Where in the chapter the problem is, if it can be pinned down.
+    pub(crate) location: Option<Location>,
+
+    pub(crate) message: String,
+
+    /// A hint on how to fix the problem.
+    pub(crate) help: Option<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            location: None,
+            message: message.into(),
+            help: None,
+        }
+    }
+
+    pub(crate) fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    pub(crate) fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            path,
+            location,
+            message,
+            help,
+        } = self;
+        write!(f, "{}", path.display())?;
+        if let Some(location) = location {
+            write!(f, ":{}:{}", location.line, location.column)?;
+        }
+        write!(f, ": {message}")?;
+
+        let gutter_width = location
+            .as_ref()
+            .map(|location| location.line.to_string().len())
+            .unwrap_or_default();
+        let gutter = " ".repeat(gutter_width);
+        if let Some(location) = location {
+            let Location {
+                line,
+                source_line,
+                underline_start,
+                underline_len,
+                ..
+            } = location;
+            let underline_indent = source_line[..*underline_start]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let underline = "^".repeat((*underline_len).max(1));
+            write!(
+                f,
+                "\n{gutter} |\n{line} | {source_line}\n{gutter} | {underline_indent}{underline}"
+            )?;
+        }
+        if let Some(help) = help {
+            write!(f, "\n{gutter} = help: {help}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A problem found while checking a book.
+#[derive(Debug)]
+pub struct Problem {
+    pub(crate) severity: Severity,
+    diagnostic: Diagnostic,
+}
+
+impl Problem {
+    pub(crate) fn new(severity: Severity, diagnostic: Diagnostic) -> Self {
+        Self {
+            severity,
+            diagnostic,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.diagnostic.fmt(f)
+    }
+}
+
+/// How serious a problem is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// A problem which should be fixed.
+    Error,
+
+    /// A problem which may be intended.
+    Warning,
+}
+
+/// A position in the source of a chapter.
+#[derive(Debug)]
+pub(crate) struct Location {
+    /// The 1-based line number.
+    pub(crate) line: usize,
+
+    /// The 1-based column number, in characters.
+    pub(crate) column: usize,
+
+    /// The full text of the line.
+    source_line: String,
+
+    /// The byte offset into `source_line` of the text to highlight.
+    underline_start: usize,
+
+    /// The number of characters to highlight.
+    underline_len: usize,
+}
+
+impl Location {
+    /// Computes the location of the `len`-byte span at byte `offset` in `text`.
+    ///
+    /// Spans which continue onto later lines are highlighted up to the end of the first line.
+    pub(crate) fn new(text: &str, offset: usize, len: usize) -> Self {
+        let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let source_line = text[line_start..].lines().next().unwrap_or_default();
+        let underline_start = offset - line_start;
+        let underline_end = (underline_start + len).min(source_line.len());
+        Self {
+            line: text[..offset].matches('\n').count() + 1,
+            column: text[line_start..offset].chars().count() + 1,
+            source_line: source_line.to_owned(),
+            underline_start,
+            underline_len: source_line[underline_start..underline_end].chars().count(),
+        }
+    }
+}
+
+/// Returns the candidate most similar to `target`, if any is similar enough to be a likely typo.
+pub(crate) fn closest_match<'a>(
+    target: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = target.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Computes the edit distance between `lhs` and `rhs`, counting insertions, deletions,
+/// substitutions and transpositions of adjacent characters.
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let lhs = lhs.chars().collect::<Vec<_>>();
+    let rhs = rhs.chars().collect::<Vec<_>>();
+    let mut distances = vec![vec![0; rhs.len() + 1]; lhs.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=lhs.len() {
+        for j in 1..=rhs.len() {
+            let substitution_cost = usize::from(lhs[i - 1] != rhs[j - 1]);
+            let mut distance = (distances[i - 1][j - 1] + substitution_cost)
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && lhs[i - 1] == rhs[j - 2] && lhs[i - 2] == rhs[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[lhs.len()][rhs.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{eq, none, some};
+    use indoc::indoc;
+
+    #[googletest::test]
+    fn display() {
+        let text = "# Title\n\nSee \u{e9} {{#diataxis compas}} here";
+        let offset = text.find("{{").unwrap();
+        let diagnostic = Diagnostic::new("path/to/chapter.md", "unknown directive `compas`")
+            .with_location(Location::new(text, offset, "{{#diataxis compas}}".len()))
+            .with_help("did you mean `compass`?");
+        expect_that!(
+            diagnostic.to_string(),
+            eq(indoc! {"
+                path/to/chapter.md:3:7: unknown directive `compas`
+                  |
+                3 | See \u{e9} {{#diataxis compas}} here
+                  |       ^^^^^^^^^^^^^^^^^^^^
+                  = help: did you mean `compass`?"})
+        );
+    }
+
+    #[googletest::test]
+    fn closest() {
+        expect_that!(
+            closest_match("compas", ["compass", "table-of-contents"]),
+            some(eq("compass"))
+        );
+        expect_that!(
+            closest_match("how-to-guides", ["tutorial", "how-to-guide"]),
+            some(eq("how-to-guide"))
+        );
+        expect_that!(
+            closest_match("toc", ["compass", "table-of-contents"]),
+            none()
+        );
+    }
+}
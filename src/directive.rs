@@ -0,0 +1,706 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::iter;
+use std::num::NonZeroUsize;
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::diagnostic::closest_match;
+use crate::quadrant::Quadrant;
+
+const DIRECTIVE_PREFIX: &str = "{{#diataxis";
+const DIRECTIVE_SUFFIX: &str = "}}";
+const ESCAPE_CHAR: char = '\\';
+
+/// A `{{#diataxis ...}}` expression found in some text.
+pub(crate) struct DirectiveMatch<'src> {
+    /// The byte offset of the expression in the text.
+    pub(crate) start: usize,
+
+    /// The source text of the expression, including any leading escape character.
+    pub(crate) src: &'src str,
+
+    /// The parsed directive, or `None` if the expression is escaped as `\{{#diataxis ...}}`.
+    pub(crate) directive: Option<Result<Directive, DirectiveError>>,
+}
+
+/// Finds and parses all `{{#diataxis ...}}` expressions in `text`, in order.
+///
+/// Expressions in code blocks and inline code are ignored.
+pub(crate) fn find_directives(text: &str) -> impl Iterator<Item = DirectiveMatch<'_>> {
+    let code_ranges = Parser::new(text)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut offset = 0;
+    iter::from_fn(move || loop {
+        let start = offset + text[offset..].find(DIRECTIVE_PREFIX)?;
+        if let Some(code_range) = code_ranges.iter().find(|range| range.contains(&start)) {
+            offset = code_range.end.max(start + DIRECTIVE_PREFIX.len());
+            continue;
+        }
+
+        let (directive, len) = Directive::parse(&text[start..]);
+        offset = start + len;
+        if text[..start].ends_with(ESCAPE_CHAR) {
+            return Some(DirectiveMatch {
+                start: start - ESCAPE_CHAR.len_utf8(),
+                src: &text[start - ESCAPE_CHAR.len_utf8()..offset],
+                directive: None,
+            });
+        }
+        return Some(DirectiveMatch {
+            start,
+            src: &text[start..offset],
+            directive: Some(directive),
+        });
+    })
+}
+
+/// A parsed `{{#diataxis ...}}` directive.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Directive {
+    Compass(CompassArgs),
+    Toc(TocArgs),
+    Kind(KindArgs),
+}
+
+impl Directive {
+    const NAMES: [&'static str; 3] = ["compass", "table-of-contents", "kind"];
+
+    /// Parses the directive at the start of `text`, which must begin with [`DIRECTIVE_PREFIX`].
+    ///
+    /// Returns the parse result alongside the length of the source text it covers. If the
+    /// directive is unterminated, only the prefix is covered.
+    fn parse(text: &str) -> (Result<Self, DirectiveError>, usize) {
+        debug_assert!(text.starts_with(DIRECTIVE_PREFIX));
+
+        let line = text.lines().next().unwrap_or_default();
+        let Some(body_end) = line.find(DIRECTIVE_SUFFIX) else {
+            return (Err(DirectiveError::Unterminated), DIRECTIVE_PREFIX.len());
+        };
+        let len = body_end + DIRECTIVE_SUFFIX.len();
+        (
+            Self::parse_body(&line[DIRECTIVE_PREFIX.len()..body_end]),
+            len,
+        )
+    }
+
+    fn parse_body(body: &str) -> Result<Self, DirectiveError> {
+        if !body.is_empty() && !body.starts_with(char::is_whitespace) {
+            return Err(DirectiveError::MissingSpace);
+        }
+
+        let mut tokens = tokenize(body)?.into_iter();
+        let name = match tokens.next() {
+            Some(Token::Positional(name)) => name,
+            Some(Token::Named(key, _)) => return Err(DirectiveError::UnknownDirective(key.into())),
+            None => return Err(DirectiveError::MissingName),
+        };
+        let name = match Self::NAMES.iter().find(|candidate| **candidate == name) {
+            Some(name) => *name,
+            None => return Err(DirectiveError::UnknownDirective(name.into_owned())),
+        };
+
+        let mut args = RawArgs::new(name, tokens);
+        let directive = match name {
+            "compass" => Self::Compass(CompassArgs::parse(&mut args)?),
+            "table-of-contents" => Self::Toc(TocArgs::parse(&mut args)?),
+            "kind" => Self::Kind(KindArgs::parse(&mut args)?),
+            _ => unreachable!("internal error: unhandled directive name {name}"),
+        };
+        args.finish()?;
+        Ok(directive)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct CompassArgs {
+    /// The layout to use instead of the one configured in `book.toml`.
+    pub(crate) layout: Option<CompassLayout>,
+}
+
+impl CompassArgs {
+    fn parse(args: &mut RawArgs<'_>) -> Result<Self, DirectiveError> {
+        let layout = args.take("layout")?;
+        Ok(Self { layout })
+    }
+}
+
+/// How the four sections of the compass are arranged.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) enum CompassLayout {
+    /// A grid of cards.
+    #[default]
+    Grid,
+
+    /// A 2×2 grid of cards, labelled with the axes of the Diátaxis map.
+    Axes,
+
+    /// An inline SVG diagram of the Diátaxis map.
+    Svg,
+}
+
+impl ArgValue for CompassLayout {
+    const EXPECTED: &'static str = "`grid`, `axes` or `svg`";
+    const VALUES: &'static [&'static str] = &["grid", "axes", "svg"];
+
+    fn parse_arg(value: &str) -> Option<Self> {
+        match value {
+            "grid" => Some(Self::Grid),
+            "axes" => Some(Self::Axes),
+            "svg" => Some(Self::Svg),
+            _ => None,
+        }
+    }
+}
+
+impl CompassLayout {
+    /// Parses a layout as written in `book.toml`.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::parse_arg(name)
+    }
+
+    pub(crate) const fn name(&self) -> &'static str {
+        match self {
+            Self::Grid => "grid",
+            Self::Axes => "axes",
+            Self::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TocArgs {
+    /// The number of levels of sub-chapters to list.
+    pub(crate) depth: NonZeroUsize,
+
+    pub(crate) sort: TocSort,
+}
+
+impl TocArgs {
+    fn parse(args: &mut RawArgs<'_>) -> Result<Self, DirectiveError> {
+        let depth = args.take("depth")?.unwrap_or(NonZeroUsize::MIN);
+        let sort = args.take("sort")?.unwrap_or_default();
+        Ok(Self { depth, sort })
+    }
+}
+
+impl Default for TocArgs {
+    fn default() -> Self {
+        Self {
+            depth: NonZeroUsize::MIN,
+            sort: TocSort::default(),
+        }
+    }
+}
+
+/// The order in which table of contents entries are listed.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) enum TocSort {
+    /// The order given in `SUMMARY.md`.
+    #[default]
+    Summary,
+
+    /// Alphabetical order of chapter names.
+    Title,
+}
+
+impl ArgValue for TocSort {
+    const EXPECTED: &'static str = "`summary` or `title`";
+    const VALUES: &'static [&'static str] = &["summary", "title"];
+
+    fn parse_arg(value: &str) -> Option<Self> {
+        match value {
+            "summary" => Some(Self::Summary),
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct KindArgs {
+    pub(crate) quadrant: Quadrant,
+}
+
+impl KindArgs {
+    fn parse(args: &mut RawArgs<'_>) -> Result<Self, DirectiveError> {
+        let quadrant = args.take_positional("kind")?;
+        Ok(Self { quadrant })
+    }
+}
+
+impl ArgValue for Quadrant {
+    const EXPECTED: &'static str =
+        "one of `tutorial`, `how-to-guide`, `explanation` or `reference`";
+    const VALUES: &'static [&'static str] = &[
+        Self::Tutorial.name(),
+        Self::HowToGuide.name(),
+        Self::Explanation.name(),
+        Self::Reference.name(),
+    ];
+
+    fn parse_arg(value: &str) -> Option<Self> {
+        Self::from_name(value)
+    }
+}
+
+/// A value which may be passed as a directive argument.
+trait ArgValue: Sized {
+    /// A description of the accepted values, used in diagnostics.
+    const EXPECTED: &'static str;
+
+    /// The accepted values, if there are finitely many, used to suggest corrections.
+    const VALUES: &'static [&'static str] = &[];
+
+    fn parse_arg(value: &str) -> Option<Self>;
+}
+
+impl ArgValue for NonZeroUsize {
+    const EXPECTED: &'static str = "a positive integer";
+
+    fn parse_arg(value: &str) -> Option<Self> {
+        value.parse().ok()
+    }
+}
+
+/// The not-yet-interpreted arguments of a directive.
+struct RawArgs<'src> {
+    directive: &'static str,
+    positional: Vec<Cow<'src, str>>,
+    named: Vec<(&'src str, Cow<'src, str>)>,
+
+    /// The names of the arguments accepted by the directive so far.
+    known: Vec<&'static str>,
+}
+
+impl<'src> RawArgs<'src> {
+    fn new(directive: &'static str, tokens: impl IntoIterator<Item = Token<'src>>) -> Self {
+        let mut positional = vec![];
+        let mut named = vec![];
+        for token in tokens {
+            match token {
+                Token::Positional(value) => positional.push(value),
+                Token::Named(key, value) => named.push((key, value)),
+            }
+        }
+        Self {
+            directive,
+            positional,
+            named,
+            known: vec![],
+        }
+    }
+
+    /// Removes and parses the argument called `key`, if present.
+    fn take<T: ArgValue>(&mut self, key: &'static str) -> Result<Option<T>, DirectiveError> {
+        self.known.push(key);
+        let mut matches = self.named.iter().filter(|(k, _)| *k == key);
+        if matches.nth(1).is_some() {
+            return Err(DirectiveError::DuplicateArgument {
+                directive: self.directive,
+                key: key.into(),
+            });
+        }
+
+        let Some(index) = self.named.iter().position(|(k, _)| *k == key) else {
+            return Ok(None);
+        };
+        let (_, value) = self.named.remove(index);
+        T::parse_arg(&value)
+            .map(Some)
+            .ok_or_else(|| DirectiveError::InvalidValue {
+                directive: self.directive,
+                key: key.into(),
+                value: value.into_owned(),
+                expected: T::EXPECTED,
+                candidates: T::VALUES,
+            })
+    }
+
+    /// Removes and parses the next positional argument, which is described by `name`.
+    fn take_positional<T: ArgValue>(&mut self, name: &'static str) -> Result<T, DirectiveError> {
+        if self.positional.is_empty() {
+            return Err(DirectiveError::MissingArgument {
+                directive: self.directive,
+                name,
+            });
+        }
+        let value = self.positional.remove(0);
+        T::parse_arg(&value).ok_or_else(|| DirectiveError::InvalidValue {
+            directive: self.directive,
+            key: name.into(),
+            value: value.into_owned(),
+            expected: T::EXPECTED,
+            candidates: T::VALUES,
+        })
+    }
+
+    /// Checks that every argument has been consumed.
+    fn finish(self) -> Result<(), DirectiveError> {
+        if let Some((key, _)) = self.named.into_iter().next() {
+            return Err(DirectiveError::UnknownArgument {
+                directive: self.directive,
+                key: key.into(),
+                known: self.known,
+            });
+        }
+        if let Some(value) = self.positional.into_iter().next() {
+            return Err(DirectiveError::UnexpectedPositional {
+                directive: self.directive,
+                value: value.into_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'src> {
+    Positional(Cow<'src, str>),
+    Named(&'src str, Cow<'src, str>),
+}
+
+/// Splits a directive body into whitespace-separated `value` and `key=value` tokens. Values may
+/// be double-quoted to include whitespace, with `\"` and `\\` as escapes.
+fn tokenize(body: &str) -> Result<Vec<Token<'_>>, DirectiveError> {
+    let mut tokens = vec![];
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '"')
+            .unwrap_or(rest.len());
+        let (key, after_key) = rest.split_at(key_end);
+        let (token, after_token) = match after_key.strip_prefix('=') {
+            Some(value_src) => {
+                let (value, after_value) = parse_value(value_src)?;
+                (Token::Named(key, value), after_value)
+            }
+            None if key.is_empty() => {
+                let (value, after_value) = parse_value(after_key)?;
+                (Token::Positional(value), after_value)
+            }
+            None => (Token::Positional(Cow::Borrowed(key)), after_key),
+        };
+        if !after_token.is_empty() && !after_token.starts_with(char::is_whitespace) {
+            return Err(DirectiveError::MissingSpace);
+        }
+        tokens.push(token);
+        rest = after_token.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_value(src: &str) -> Result<(Cow<'_, str>, &str), DirectiveError> {
+    let Some(quoted) = src.strip_prefix('"') else {
+        let end = src.find(char::is_whitespace).unwrap_or(src.len());
+        return Ok((Cow::Borrowed(&src[..end]), &src[end..]));
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Cow::Owned(value), &quoted[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => break,
+            },
+            _ => value.push(c),
+        }
+    }
+    Err(DirectiveError::UnterminatedString)
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum DirectiveError {
+    Unterminated,
+    UnterminatedString,
+    MissingSpace,
+    MissingName,
+    UnknownDirective(String),
+    MissingArgument {
+        directive: &'static str,
+        name: &'static str,
+    },
+    UnknownArgument {
+        directive: &'static str,
+        key: String,
+        known: Vec<&'static str>,
+    },
+    UnexpectedPositional {
+        directive: &'static str,
+        value: String,
+    },
+    DuplicateArgument {
+        directive: &'static str,
+        key: String,
+    },
+    InvalidValue {
+        directive: &'static str,
+        key: String,
+        value: String,
+        expected: &'static str,
+        candidates: &'static [&'static str],
+    },
+}
+
+impl DirectiveError {
+    /// Returns the closest valid alternative to what was written, if there is a plausible one.
+    pub(crate) fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Self::UnknownDirective(name) => closest_match(name, Directive::NAMES),
+            Self::UnknownArgument { key, known, .. } => closest_match(key, known.iter().copied()),
+            Self::InvalidValue {
+                value, candidates, ..
+            } => closest_match(value, candidates.iter().copied()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unterminated => write!(f, "missing closing `{DIRECTIVE_SUFFIX}`"),
+            Self::UnterminatedString => write!(f, "unterminated string"),
+            Self::MissingSpace => write!(f, "missing whitespace between arguments"),
+            Self::MissingName => write!(
+                f,
+                "missing directive name, expected one of {}",
+                fmt_names(&Directive::NAMES)
+            ),
+            Self::UnknownDirective(name) => write!(
+                f,
+                "unknown directive `{name}`, expected one of {}",
+                fmt_names(&Directive::NAMES)
+            ),
+            Self::MissingArgument { directive, name } => {
+                write!(f, "missing {name} argument for `{directive}`")
+            }
+            Self::UnknownArgument { directive, key, .. } => {
+                write!(f, "unknown argument `{key}` for `{directive}`")
+            }
+            Self::UnexpectedPositional { directive, value } => {
+                write!(f, "unexpected argument `{value}` for `{directive}`")
+            }
+            Self::DuplicateArgument { directive, key } => {
+                write!(f, "argument `{key}` given more than once for `{directive}`")
+            }
+            Self::InvalidValue {
+                directive,
+                key,
+                value,
+                expected,
+                ..
+            } => write!(
+                f,
+                "invalid value `{value}` for argument `{key}` of `{directive}`, expected {expected}"
+            ),
+        }
+    }
+}
+
+fn fmt_names(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{elements_are, eq, err, none, ok, some};
+    use indoc::indoc;
+
+    fn parse(text: &str) -> Result<Directive, DirectiveError> {
+        let (result, len) = Directive::parse(text);
+        if result.is_ok() {
+            assert_eq!(len, text.len());
+        }
+        result
+    }
+
+    #[googletest::test]
+    fn valid() {
+        expect_that!(
+            parse("{{#diataxis compass}}"),
+            ok(eq(&Directive::Compass(CompassArgs::default())))
+        );
+        expect_that!(
+            parse("{{#diataxis compass layout=axes}}"),
+            ok(eq(&Directive::Compass(CompassArgs {
+                layout: Some(CompassLayout::Axes),
+            })))
+        );
+        expect_that!(
+            parse("{{#diataxis  table-of-contents }}"),
+            ok(eq(&Directive::Toc(TocArgs::default())))
+        );
+        expect_that!(
+            parse("{{#diataxis table-of-contents depth=3 sort=title}}"),
+            ok(eq(&Directive::Toc(TocArgs {
+                depth: NonZeroUsize::new(3).unwrap(),
+                sort: TocSort::Title,
+            })))
+        );
+        expect_that!(
+            parse("{{#diataxis kind how-to-guide}}"),
+            ok(eq(&Directive::Kind(KindArgs {
+                quadrant: Quadrant::HowToGuide
+            })))
+        );
+        expect_that!(
+            parse(r#"{{#diataxis table-of-contents sort="summary"}}"#),
+            ok(eq(&Directive::Toc(TocArgs {
+                sort: TocSort::Summary,
+                ..TocArgs::default()
+            })))
+        );
+    }
+
+    #[googletest::test]
+    fn invalid() {
+        expect_that!(
+            parse("{{#diataxis compass"),
+            err(eq(&DirectiveError::Unterminated))
+        );
+        expect_that!(
+            parse("{{#diataxis}}"),
+            err(eq(&DirectiveError::MissingName))
+        );
+        expect_that!(
+            parse("{{#diataxiscompass}}"),
+            err(eq(&DirectiveError::MissingSpace))
+        );
+        expect_that!(
+            parse("{{#diataxis compas}}"),
+            err(eq(&DirectiveError::UnknownDirective("compas".into())))
+        );
+        expect_that!(
+            parse("{{#diataxis compass sort=title}}"),
+            err(eq(&DirectiveError::UnknownArgument {
+                directive: "compass",
+                key: "sort".into(),
+                known: vec!["layout"],
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis compass extra}}"),
+            err(eq(&DirectiveError::UnexpectedPositional {
+                directive: "compass",
+                value: "extra".into(),
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis table-of-contents sort=title sort=summary}}"),
+            err(eq(&DirectiveError::DuplicateArgument {
+                directive: "table-of-contents",
+                key: "sort".into(),
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis table-of-contents sort=size}}"),
+            err(eq(&DirectiveError::InvalidValue {
+                directive: "table-of-contents",
+                key: "sort".into(),
+                value: "size".into(),
+                expected: TocSort::EXPECTED,
+                candidates: TocSort::VALUES,
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis table-of-contents depth=0}}"),
+            err(eq(&DirectiveError::InvalidValue {
+                directive: "table-of-contents",
+                key: "depth".into(),
+                value: "0".into(),
+                expected: NonZeroUsize::EXPECTED,
+                candidates: &[],
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis kind}}"),
+            err(eq(&DirectiveError::MissingArgument {
+                directive: "kind",
+                name: "kind",
+            }))
+        );
+        expect_that!(
+            parse("{{#diataxis kind tutorials}}"),
+            err(eq(&DirectiveError::InvalidValue {
+                directive: "kind",
+                key: "kind".into(),
+                value: "tutorials".into(),
+                expected: Quadrant::EXPECTED,
+                candidates: Quadrant::VALUES,
+            }))
+        );
+        expect_that!(
+            parse(r#"{{#diataxis table-of-contents sort="title}}"#),
+            err(eq(&DirectiveError::UnterminatedString))
+        );
+    }
+
+    #[googletest::test]
+    fn code_is_ignored() {
+        let text = indoc! {r#"
+            {{#diataxis compass}}
+
+            ```
+            {{#diataxis compass}}
+            ```
+
+                {{#diataxis compass}}
+
+            Inline `{{#diataxis compass}}` code and `` {{#diataxis compass}} `` spans.
+
+            Escaped \{{#diataxis compass}}.
+        "#};
+        let found = find_directives(text)
+            .map(|directive_match| (directive_match.src, directive_match.directive))
+            .collect::<Vec<_>>();
+        expect_that!(
+            found,
+            elements_are![
+                eq(&(
+                    "{{#diataxis compass}}",
+                    Some(Ok(Directive::Compass(CompassArgs::default())))
+                )),
+                eq(&("\\{{#diataxis compass}}", None)),
+            ]
+        );
+    }
+
+    #[googletest::test]
+    fn suggestions() {
+        let suggestion = |text| parse(text).unwrap_err().suggestion();
+        expect_that!(suggestion("{{#diataxis compas}}"), some(eq("compass")));
+        expect_that!(suggestion("{{#diataxis toc}}"), none());
+        expect_that!(
+            suggestion("{{#diataxis table-of-contents dept=2}}"),
+            some(eq("depth"))
+        );
+        expect_that!(
+            suggestion("{{#diataxis table-of-contents sort=titel}}"),
+            some(eq("title"))
+        );
+        expect_that!(
+            suggestion("{{#diataxis kind tutorials}}"),
+            some(eq("tutorial"))
+        );
+    }
+}
@@ -1,67 +1,219 @@
+mod config;
+mod diagnostic;
+mod directive;
+mod link;
+mod markup;
+mod quadrant;
+mod rewrite;
+mod template;
+mod translation;
+
+pub use crate::diagnostic::{Problem, Severity};
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::iter;
 use std::path::{Component, Path, PathBuf};
-use std::sync::LazyLock;
 
-use aho_corasick::{AhoCorasick, MatchKind};
 use anyhow::{anyhow, Context, Result};
-use indoc::writedoc;
+use indoc::{formatdoc, writedoc};
 use mdbook::book::{Book, Chapter};
 use mdbook::errors::Result as MdbookResult;
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 use mdbook::BookItem;
-use toml::value::Table;
+use serde_json::{json, Value};
+
+use crate::config::{CompassCard, Config, DirectiveHandling, KindPatterns, RendererConfig};
+use crate::diagnostic::{closest_match, Diagnostic, Location};
+use crate::directive::{
+    find_directives, CompassArgs, CompassLayout, Directive, KindArgs, TocArgs, TocSort,
+};
+use crate::link::Link;
+use crate::markup::{escape_html, escape_markdown, markdown_to_plain_text, render_inline_markdown};
+use crate::quadrant::Quadrant;
+use crate::rewrite::insert_auto_tocs;
+use crate::template::Templates;
+use crate::translation::AxisLabels;
 
 #[derive(Default)]
-pub struct DiataxisPreprocessor;
+pub struct DiataxisPreprocessor {
+    renderers: RendererConfig,
+}
 
 impl DiataxisPreprocessor {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Creates a preprocessor which reports the renderers it supports according to the
+    /// `[preprocessor.diataxis]` table of `config`.
+    pub fn with_book_config(config: &mdbook::Config) -> Result<Self> {
+        let renderers = config
+            .get_preprocessor("diataxis")
+            .and_then(|raw| raw.get("renderers"))
+            .map(RendererConfig::new)
+            .transpose()
+            .context("cannot parse `renderers` field")?
+            .unwrap_or_default();
+        Ok(Self { renderers })
+    }
+
+    /// Checks the Diátaxis structure of `book`, whose root directory is `root` and which is
+    /// configured by `book_config`, without rendering any directives.
+    ///
+    /// As well as the problems reported while preprocessing, this reports compass links to missing
+    /// pages even if the book has no compass, pages which belong to no quadrant, quadrants which
+    /// have no pages, and pages whose kind does not match where they are in the book.
+    pub fn check(
+        &self,
+        root: &Path,
+        book_config: &mdbook::Config,
+        mut book: Book,
+    ) -> Result<Vec<Problem>> {
+        let mut config = Config::from_book_config(book_config)?;
+
+        let mut diagnostics = vec![];
+        let index = self.preprocess_book(root, &mut config, &mut book, None, &mut diagnostics)?;
+        if !index.uses_compass {
+            config.check_links(&index, &mut diagnostics);
+        }
+        let mut problems = diagnostics
+            .into_iter()
+            .map(|diagnostic| Problem::new(Severity::Error, diagnostic))
+            .collect::<Vec<_>>();
+        config.check_structure(&book, &index, &mut problems);
+
+        if config.strict {
+            for problem in &mut problems {
+                problem.severity = Severity::Error;
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Prepares `book` and expands or removes its directives, returning the index of the prepared
+    /// book.
+    fn preprocess_book(
+        &self,
+        root: &Path,
+        config: &mut Config,
+        book: &mut Book,
+        format: Option<OutputFormat>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<BookIndex> {
+        config.resolve_chapter_links(book)?;
+        let templates = Templates::load(root, config.templates.iter().copied())?;
+
+        let mut index = BookIndex::new(book, &config.kind_patterns, diagnostics);
+        if config.landing_pages {
+            config.insert_landing_pages(book, &mut index, format, diagnostics);
+        }
+        if config.auto_toc {
+            insert_auto_tocs(book);
+        }
+        if let Some(auto_compass) = &config.auto_compass
+            && auto_compass.insert_into(book)
+        {
+            index.uses_compass = true;
+        }
+        if index.uses_compass {
+            config.check_links(&index, diagnostics);
+        }
+        let preprocess_ctx = PreprocessCtx {
+            config,
+            index: &index,
+            templates: &templates,
+            format,
+        };
+        for section in &mut book.sections {
+            self.preprocess_bookitem(section, &preprocess_ctx, diagnostics)?;
+        }
+        Ok(index)
     }
 
-    fn preprocess_bookitem(&self, item: &mut BookItem, config: &Config) -> Result<()> {
+    fn preprocess_bookitem(
+        &self,
+        item: &mut BookItem,
+        ctx: &PreprocessCtx,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<()> {
         match item {
-            BookItem::Chapter(chapter) => self.preprocess_chapter(chapter, config),
+            BookItem::Chapter(chapter) => self.preprocess_chapter(chapter, ctx, diagnostics),
             BookItem::Separator | BookItem::PartTitle(_) => Ok(()),
         }
     }
 
-    fn preprocess_chapter(&self, chapter: &mut Chapter, config: &Config) -> Result<()> {
-        chapter.content = self.preprocess_content(&chapter.content, config, chapter);
+    fn preprocess_chapter(
+        &self,
+        chapter: &mut Chapter,
+        ctx: &PreprocessCtx,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<()> {
+        chapter.content = self.preprocess_content(&chapter.content, ctx, chapter, diagnostics)?;
 
         for sub_item in &mut chapter.sub_items {
-            self.preprocess_bookitem(sub_item, config)?;
+            self.preprocess_bookitem(sub_item, ctx, diagnostics)?;
         }
 
         Ok(())
     }
 
-    fn preprocess_content(&self, text: &str, config: &Config, chapter: &Chapter) -> String {
-        static MATCHER: LazyLock<AhoCorasick> = LazyLock::new(|| {
-            AhoCorasick::builder()
-                .match_kind(MatchKind::LeftmostLongest)
-                .build(Replacement::patterns())
-                .unwrap()
+    fn preprocess_content(
+        &self,
+        text: &str,
+        ctx: &PreprocessCtx,
+        chapter: &Chapter,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<String> {
+        let PreprocessCtx {
+            config,
+            index,
+            templates,
+            format,
+        } = *ctx;
+        let replacement_ctx = format.map(|format| ReplacementCtx {
+            config,
+            index,
+            templates,
+            format,
+            chapter,
         });
-
-        let replacement_ctx = ReplacementCtx { config, chapter };
         let mut ret = String::with_capacity(text.len());
-        MATCHER.replace_all_with(text, &mut ret, |result, _, ret| {
-            let replacement = Replacement::from_pattern_index(result.pattern().as_usize());
-            replacement.write_to(ret, &replacement_ctx);
-            if replacement.is_malformed() {
-                eprintln!(
-                    "Warning: malformed `{{{{#diataxis ...}}}}` expression in {}",
-                    chapter
-                        .source_path
-                        .as_deref()
-                        .expect("internal error: draft chapter has content")
-                        .display(),
-                )
+        let mut copied_up_to = 0;
+        for directive_match in find_directives(text) {
+            ret.push_str(&text[copied_up_to..directive_match.start]);
+            copied_up_to = directive_match.start + directive_match.src.len();
+
+            match directive_match.directive {
+                None => ret.push_str(&directive_match.src[1..]),
+                Some(Ok(directive)) => {
+                    if let Some(replacement_ctx) = &replacement_ctx {
+                        directive.write_to(&mut ret, replacement_ctx)?;
+                    }
+                }
+                Some(Err(err)) => {
+                    let mut diagnostic = Diagnostic::new(
+                        chapter
+                            .source_path
+                            .as_deref()
+                            .expect("internal error: draft chapter has content"),
+                        format!("malformed `{{{{#diataxis ...}}}}` expression: {err}"),
+                    )
+                    .with_location(Location::new(
+                        text,
+                        directive_match.start,
+                        directive_match.src.len(),
+                    ));
+                    if let Some(suggestion) = err.suggestion() {
+                        diagnostic = diagnostic.with_help(format!("did you mean `{suggestion}`?"));
+                    }
+                    diagnostics.push(diagnostic);
+                    ret.push_str(directive_match.src);
+                }
             }
-            true
-        });
-        ret
+        }
+        ret.push_str(&text[copied_up_to..]);
+        Ok(ret)
     }
 }
 
@@ -71,361 +223,1040 @@ impl Preprocessor for DiataxisPreprocessor {
     }
 
     fn supports_renderer(&self, renderer: &str) -> bool {
-        renderer == "html"
+        self.renderers.handling(renderer) != DirectiveHandling::Keep
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> MdbookResult<Book> {
-        let config = ctx
-            .config
-            .get_preprocessor("diataxis")
-            .map(Config::new)
-            .transpose()?
-            .unwrap_or_default();
+        let mut config = Config::from_book_config(&ctx.config)?;
+        let format = match config.renderers.handling(&ctx.renderer) {
+            DirectiveHandling::Keep => return Ok(book),
+            DirectiveHandling::Strip => None,
+            DirectiveHandling::Expand(format) => Some(format),
+        };
+        let mut diagnostics = vec![];
+        let index =
+            self.preprocess_book(&ctx.root, &mut config, &mut book, format, &mut diagnostics)?;
+        if config.kind_patterns.report_unclassified {
+            diagnostics.extend(
+                index
+                    .unclassified
+                    .iter()
+                    .map(|path| unclassified_page(path)),
+            );
+        }
 
-        for section in &mut book.sections {
-            self.preprocess_bookitem(section, &config)?;
+        if config.strict && !diagnostics.is_empty() {
+            let problems = diagnostics
+                .iter()
+                .map(|diagnostic| format!("\n\n{diagnostic}"))
+                .collect::<String>();
+            return Err(anyhow!(
+                "found {} problem(s) while preprocessing the book:{problems}",
+                diagnostics.len(),
+            ));
+        }
+        for diagnostic in &diagnostics {
+            eprintln!("Warning: {diagnostic}");
         }
 
         Ok(book)
     }
 }
 
-#[derive(Debug, Default)]
-struct Config<'cfg> {
-    tutorials: SectionConfig<'cfg>,
-    how_to_guides: SectionConfig<'cfg>,
-    reference: SectionConfig<'cfg>,
-    explanation: SectionConfig<'cfg>,
+/// The state shared by all chapters while preprocessing.
+#[derive(Copy, Clone)]
+struct PreprocessCtx<'ctx> {
+    config: &'ctx Config<'ctx>,
+    index: &'ctx BookIndex,
+    templates: &'ctx Templates,
+
+    /// The format in which to expand directives, or `None` if they are removed.
+    format: Option<OutputFormat>,
 }
 
-impl<'cfg> Config<'cfg> {
-    fn new(raw: &'cfg Table) -> Result<Self> {
-        let section_overrides = |section| -> Result<SectionConfig<'_>> {
-            let overrides = raw
-                .get("compass")
-                .map(|compass_value| {
-                    compass_value
-                        .as_table()
-                        .ok_or_else(|| anyhow!("`compass` field must be a table"))
-                })
-                .transpose()?
-                .and_then(|compass_table| compass_table.get(section))
-                .map(|section_value| {
-                    section_value
-                        .as_table()
-                        .ok_or_else(|| anyhow!("`compass.{section}` field must be a table"))
-                })
-                .transpose()?
-                .map(|section_table| {
-                    SectionConfig::new(section_table)
-                        .with_context(|| anyhow!("cannot parse `compass.{section}` table"))
-                })
-                .transpose()?
-                .unwrap_or_default();
-            Ok(overrides)
-        };
-        let tutorials = section_overrides("tutorials")?;
-        let how_to_guides = section_overrides("how-to-guides")?;
-        let explanation = section_overrides("explanation")?;
-        let reference = section_overrides("reference")?;
-        Ok(Self {
-            tutorials,
-            how_to_guides,
-            explanation,
-            reference,
-        })
-    }
+/// The format in which directives are expanded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum OutputFormat {
+    Html,
 
-    fn tutorials_title(&self) -> &str {
-        self.tutorials.title_override.unwrap_or("Tutorials")
-    }
+    /// Plain markdown, for renderers which may not support embedded HTML.
+    Markdown,
 
-    fn tutorials_description(&self) -> &str {
-        self.tutorials
-            .description_override
-            .unwrap_or("Hands-on lessons")
-    }
+    /// Text without links or markup beyond lists.
+    Text,
+}
 
-    fn tutorials_link(&self) -> &Path {
-        self.tutorials
-            .link_override
-            .as_deref()
-            .unwrap_or(Path::new("./tutorials/index.html"))
+impl OutputFormat {
+    fn for_renderer(renderer: &str) -> Self {
+        match renderer {
+            "html" => Self::Html,
+            _ => Self::Markdown,
+        }
     }
+}
 
-    fn how_to_guides_title(&self) -> &str {
-        self.how_to_guides.title_override.unwrap_or("How-to guides")
-    }
+/// Information about the whole book, gathered before any chapter is preprocessed.
+#[derive(Debug, Default)]
+struct BookIndex {
+    /// The declared kind of each chapter, keyed by source path.
+    kinds: HashMap<PathBuf, Quadrant>,
 
-    fn how_to_guides_description(&self) -> &str {
-        self.how_to_guides
-            .description_override
-            .unwrap_or("Step-by-step instructions for common tasks")
-    }
+    /// The paths of all rendered pages, relative to the book's source directory.
+    pages: BTreeSet<PathBuf>,
 
-    fn how_to_guides_link(&self) -> &Path {
-        self.how_to_guides
-            .link_override
-            .as_deref()
-            .unwrap_or(Path::new("./how-to/index.html"))
-    }
+    /// The rendered pages of each chapter's ancestors, nearest first, keyed by source path.
+    ancestors: HashMap<PathBuf, Vec<PathBuf>>,
 
-    fn explanation_title(&self) -> &str {
-        self.explanation.title_override.unwrap_or("Explanation")
-    }
+    /// The source paths of chapters whose kind is neither declared nor inferred, in book order.
+    unclassified: Vec<PathBuf>,
 
-    fn explanation_description(&self) -> &str {
-        self.explanation
-            .description_override
-            .unwrap_or("Long-form discussion of key topics")
-    }
+    /// Whether any chapter contains a compass.
+    uses_compass: bool,
+}
 
-    fn explanation_link(&self) -> &Path {
-        self.explanation
-            .link_override
-            .as_deref()
-            .unwrap_or(Path::new("./explanations/index.html"))
+impl BookIndex {
+    fn new(book: &Book, kind_patterns: &KindPatterns, diagnostics: &mut Vec<Diagnostic>) -> Self {
+        let mut kinds = HashMap::new();
+        let mut pages = BTreeSet::new();
+        let mut unclassified = vec![];
+        let mut uses_compass = false;
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            if let Some(Link::Internal { path, .. }) = chapter
+                .path
+                .as_deref()
+                .map(|path| Link::to_chapter(path).rendered())
+            {
+                pages.insert(path);
+            }
+            let Some(source_path) = &chapter.source_path else {
+                continue;
+            };
+            for directive_match in find_directives(&chapter.content) {
+                let quadrant = match directive_match.directive {
+                    Some(Ok(Directive::Kind(KindArgs { quadrant }))) => quadrant,
+                    Some(Ok(Directive::Compass(_))) => {
+                        uses_compass = true;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                match kinds.entry(source_path.clone()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(quadrant);
+                    }
+                    Entry::Occupied(entry) if *entry.get() != quadrant => diagnostics.push(
+                        Diagnostic::new(
+                            source_path,
+                            format!(
+                                "conflicting kinds `{}` and `{quadrant}` declared",
+                                entry.get()
+                            ),
+                        )
+                        .with_location(Location::new(
+                            &chapter.content,
+                            directive_match.start,
+                            directive_match.src.len(),
+                        )),
+                    ),
+                    Entry::Occupied(_) => {}
+                }
+            }
+            if kinds.contains_key(source_path) {
+                continue;
+            }
+            match kind_patterns.matching(source_path)[..] {
+                [] => unclassified.push(source_path.clone()),
+                [quadrant] => {
+                    kinds.insert(source_path.clone(), quadrant);
+                }
+                [quadrant, other, ..] => diagnostics.push(
+                    Diagnostic::new(
+                        source_path,
+                        format!("page matches patterns for both `{quadrant}` and `{other}`"),
+                    )
+                    .with_help("declare its kind with `{{#diataxis kind <kind>}}`"),
+                ),
+            }
+        }
+        let mut ancestors = HashMap::new();
+        Self::record_ancestors(&book.sections, &mut vec![], &mut ancestors);
+        Self {
+            kinds,
+            pages,
+            ancestors,
+            unclassified,
+            uses_compass,
+        }
     }
 
-    fn reference_title(&self) -> &str {
-        self.reference.title_override.unwrap_or("Reference")
+    fn record_ancestors(
+        items: &[BookItem],
+        stack: &mut Vec<PathBuf>,
+        ancestors: &mut HashMap<PathBuf, Vec<PathBuf>>,
+    ) {
+        for item in items {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            if let Some(source_path) = &chapter.source_path {
+                ancestors.insert(source_path.clone(), stack.iter().rev().cloned().collect());
+            }
+            let page = chapter
+                .path
+                .as_deref()
+                .map(|path| Link::to_chapter(path).rendered());
+            let pushed = match page {
+                Some(Link::Internal { path, .. }) => {
+                    stack.push(path);
+                    true
+                }
+                _ => false,
+            };
+            Self::record_ancestors(&chapter.sub_items, stack, ancestors);
+            if pushed {
+                stack.pop();
+            }
+        }
     }
 
-    fn reference_description(&self) -> &str {
-        self.reference
-            .description_override
-            .unwrap_or("Technical information")
+    /// Returns whether `path`, relative to the book's source directory, is a rendered page.
+    fn has_page(&self, path: &Path) -> bool {
+        self.pages.contains(&normalize(path))
     }
 
-    fn reference_link(&self) -> &Path {
-        self.reference
-            .link_override
-            .as_deref()
-            .unwrap_or(Path::new("./reference-materials/index.html"))
+    /// Returns the kind declared by `chapter`, if any.
+    fn kind(&self, chapter: &Chapter) -> Option<Quadrant> {
+        self.kinds.get(chapter.source_path.as_deref()?).copied()
     }
 }
 
-#[derive(Debug, Default)]
-struct SectionConfig<'cfg> {
-    title_override: Option<&'cfg str>,
-    description_override: Option<&'cfg str>,
-    link_override: Option<PathBuf>,
-}
+impl Config<'_> {
+    /// Checks that the compass links to pages which exist in the book.
+    fn check_links(&self, index: &BookIndex, diagnostics: &mut Vec<Diagnostic>) {
+        for card in self.compass_cards() {
+            let section = card.name();
+            let Link::Internal { path, .. } = self.card_link(card).rendered() else {
+                continue;
+            };
+            if path.as_os_str().is_empty() || index.has_page(&path) {
+                continue;
+            }
+            let mut diagnostic = Diagnostic::new(
+                "book.toml",
+                format!(
+                    "compass link for `{section}` points to `{}`, which is not a page in the book",
+                    normalize(&path).display()
+                ),
+            );
+            let page_names = index.pages.iter().filter_map(|page| page.to_str());
+            if let Some(suggestion) = path
+                .to_str()
+                .and_then(|path| closest_match(path, page_names))
+            {
+                diagnostic = diagnostic.with_help(format!("did you mean `{suggestion}`?"));
+            }
+            diagnostics.push(diagnostic);
+        }
+    }
 
-impl<'cfg> SectionConfig<'cfg> {
-    fn new(config_table: &'cfg Table) -> Result<Self> {
-        let title_override = config_table
-            .get("title")
-            .map(|title| {
-                title
-                    .as_str()
-                    .ok_or_else(|| anyhow!("`title` field must be a string"))
-            })
-            .transpose()?;
-        let description_override = config_table
-            .get("description")
-            .map(|desc| {
-                desc.as_str()
-                    .ok_or_else(|| anyhow!("`description` field must be a string"))
-            })
-            .transpose()?;
-        let link_override = config_table
-            .get("link")
-            .map(|file| {
-                file.as_str()
-                    .ok_or_else(|| anyhow!("`link` field must be a string"))
-            })
-            .transpose()?
-            .map(Path::new)
-            .map(|path| {
-                if path
-                    .file_name()
-                    .is_some_and(|file_name| file_name == "README.md")
-                {
-                    return path.with_file_name("index.html");
-                }
-                path.to_owned()
+    /// Returns the quadrant whose compass link points to `chapter` or to its nearest ancestor which
+    /// any compass link points to.
+    fn located_quadrant(&self, index: &BookIndex, chapter: &Chapter) -> Option<Quadrant> {
+        let Some(Link::Internal { path: page, .. }) = chapter
+            .path
+            .as_deref()
+            .map(|path| Link::to_chapter(path).rendered())
+        else {
+            return None;
+        };
+        let ancestors = chapter
+            .source_path
+            .as_ref()
+            .and_then(|source_path| index.ancestors.get(source_path))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let links = [
+            (Quadrant::Tutorial, self.tutorials_link().rendered()),
+            (Quadrant::HowToGuide, self.how_to_guides_link().rendered()),
+            (Quadrant::Explanation, self.explanation_link().rendered()),
+            (Quadrant::Reference, self.reference_link().rendered()),
+        ];
+        iter::once(&page).chain(ancestors).find_map(|page| {
+            links.iter().find_map(|(quadrant, link)| match link {
+                Link::Internal { path, .. } if normalize(path) == *page => Some(*quadrant),
+                _ => None,
             })
-            .map(|mut path| {
-                path.set_extension("html");
-                path
-            });
-        Ok(Self {
-            title_override,
-            description_override,
-            link_override,
         })
     }
-}
 
-#[derive(Copy, Clone)]
-enum Replacement {
-    Compass,
-    Toc,
-    Malformed,
-}
+    /// Reports pages which belong to no quadrant, quadrants in the compass which have no pages, and
+    /// pages whose kind disagrees with where they are in the book.
+    fn check_structure(&self, book: &Book, index: &BookIndex, problems: &mut Vec<Problem>) {
+        let mut populated = HashSet::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let Some(source_path) = &chapter.source_path else {
+                continue;
+            };
+            let kind = index.kind(chapter);
+            let located = self.located_quadrant(index, chapter);
+            match (kind, located) {
+                (None, None) => problems.push(Problem::new(
+                    Severity::Warning,
+                    unclassified_page(source_path),
+                )),
+                (None, Some(_)) if self.kind_patterns.report_unclassified => problems.push(
+                    Problem::new(Severity::Warning, unclassified_page(source_path)),
+                ),
+                (Some(kind), Some(located)) if kind != located => problems.push(Problem::new(
+                    Severity::Error,
+                    Diagnostic::new(
+                        source_path,
+                        format!(
+                            "page has kind `{kind}` but is in the `{}` section",
+                            located.compass_section()
+                        ),
+                    )
+                    .with_help(format!(
+                        "move the page to the `{}` section or change its kind",
+                        kind.compass_section()
+                    )),
+                )),
+                _ => {}
+            }
+            if let Some(kind) = kind
+                && let [inferred] = self.kind_patterns.matching(source_path)[..]
+                && inferred != kind
+            {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    Diagnostic::new(
+                        source_path,
+                        format!(
+                            "page is declared as `{kind}` but its path matches the patterns for \
+                             `{inferred}` in `kinds`"
+                        ),
+                    ),
+                ));
+            }
+            populated.extend(kind.or(located));
+        }
 
-impl Replacement {
-    const fn patterns() -> [&'static str; 3] {
-        [
-            Self::Compass.pattern(),
-            Self::Toc.pattern(),
-            Self::Malformed.pattern(),
-        ]
+        for card in self.compass_cards() {
+            let CompassCard::Quadrant(quadrant) = card else {
+                continue;
+            };
+            if populated.contains(&quadrant) {
+                continue;
+            }
+            problems.push(Problem::new(
+                Severity::Warning,
+                Diagnostic::new(
+                    "book.toml",
+                    format!(
+                        "no pages belong to the `{}` section",
+                        quadrant.compass_section()
+                    ),
+                ),
+            ));
+        }
     }
+}
+
+/// Reports that the page at `source_path` has no kind.
+fn unclassified_page(source_path: &Path) -> Diagnostic {
+    Diagnostic::new(source_path, "page has no kind").with_help(
+        "declare one with `{{#diataxis kind <kind>}}` or add a pattern which matches this page to \
+         `kinds`",
+    )
+}
 
-    const fn pattern(&self) -> &'static str {
+impl Directive {
+    fn write_to(&self, buf: &mut String, ctx: &ReplacementCtx) -> Result<()> {
         match self {
-            Self::Compass => "{{#diataxis compass}}",
-            Self::Toc => "{{#diataxis table-of-contents}}",
-            Self::Malformed => "{{#diataxis",
-        }
+            Self::Compass(args) => self.write_compass_to(buf, args, ctx)?,
+            Self::Toc(args) => self.write_toc_to(buf, args, ctx)?,
+            Self::Kind(args) => self.write_kind_badge_to(buf, args, ctx),
+        };
+        Ok(())
     }
 
-    fn from_pattern_index(index: usize) -> Self {
-        [Self::Compass, Self::Toc, Self::Malformed][index]
+    fn write_compass_to(
+        &self,
+        buf: &mut String,
+        args: &CompassArgs,
+        ctx: &ReplacementCtx,
+    ) -> Result<()> {
+        match ctx.format {
+            OutputFormat::Html => {
+                let sections = ctx.compass_sections();
+                let mut data = json!({
+                    "sections": sections.iter().map(CompassSection::to_json).collect::<Vec<_>>(),
+                    "current_page": ctx.current_page_json(),
+                    "layout": args.layout.unwrap_or(ctx.config.layout).name(),
+                });
+                for section in sections.iter().filter(|section| section.quadrant.is_some()) {
+                    data[section.key] = section.to_json();
+                }
+                match ctx.templates.render("compass", &data) {
+                    Some(rendered) => buf.push_str(&rendered?),
+                    None => self.write_html_compass_to(buf, args, ctx),
+                }
+            }
+            OutputFormat::Markdown | OutputFormat::Text => {
+                self.write_markdown_compass_to(buf, args, ctx)
+            }
+        }
+        Ok(())
     }
 
-    fn is_malformed(&self) -> bool {
-        matches!(self, Self::Malformed)
-    }
+    fn write_html_compass_to(&self, buf: &mut String, args: &CompassArgs, ctx: &ReplacementCtx) {
+        use std::fmt::Write;
 
-    fn write_to(&self, buf: &mut String, ctx: &ReplacementCtx) {
-        match self {
-            Self::Compass => self.write_compass_to(buf, ctx),
-            Self::Toc => self.write_toc_to(buf, ctx),
-            Self::Malformed => buf.push_str(self.pattern()),
-        };
+        let sections = ctx.compass_sections();
+        match args.layout.unwrap_or(ctx.config.layout) {
+            CompassLayout::Svg => Self::write_svg_compass_to(buf, &sections, ctx),
+            CompassLayout::Grid => {
+                writeln!(buf, "<span>\n    <div class=\"quote-grid\">")
+                    .expect("internal error: cannot to write to string");
+                for section in &sections {
+                    Self::write_html_card_to(buf, section, 2);
+                }
+                writeln!(buf, "    </div>\n</span>")
+                    .expect("internal error: cannot to write to string");
+            }
+            CompassLayout::Axes => {
+                let AxisLabels {
+                    action,
+                    cognition,
+                    acquisition,
+                    application,
+                } = ctx.config.axis_labels();
+                writedoc!(
+                    buf,
+                    r#"
+                        <span>
+                            <div class="diataxis-axes">
+                                <div class="diataxis-axis-label diataxis-axis-action">{action}</div>
+                                <div class="diataxis-axis-label diataxis-axis-acquisition">{acquisition}</div>
+                                <div class="quote-grid diataxis-axes-grid">
+                    "#,
+                )
+                .expect("internal error: cannot to write to string");
+                let (map, extras) = Self::arrange_on_map(&sections);
+                for section in map {
+                    match section {
+                        Some(section) => Self::write_html_card_to(buf, section, 3),
+                        None => writeln!(
+                            buf,
+                            "            <div class=\"diataxis-card-placeholder\"></div>"
+                        )
+                        .expect("internal error: cannot to write to string"),
+                    }
+                }
+                let closing = formatdoc!(
+                    r#"
+                            </div>
+                            <div class="diataxis-axis-label diataxis-axis-application">{application}</div>
+                            <div class="diataxis-axis-label diataxis-axis-cognition">{cognition}</div>
+                        </div>
+                    "#,
+                );
+                for line in closing.lines() {
+                    writeln!(buf, "    {line}").expect("internal error: cannot to write to string");
+                }
+                if !extras.is_empty() {
+                    writeln!(buf, "    <div class=\"quote-grid\">")
+                        .expect("internal error: cannot to write to string");
+                    for section in extras {
+                        Self::write_html_card_to(buf, section, 2);
+                    }
+                    writeln!(buf, "    </div>").expect("internal error: cannot to write to string");
+                }
+                writeln!(buf, "</span>").expect("internal error: cannot to write to string");
+            }
+        }
     }
 
-    fn write_compass_to(&self, buf: &mut String, ctx: &ReplacementCtx) {
+    /// Writes the compass as an SVG diagram, whose colours follow the book's theme.
+    fn write_svg_compass_to(buf: &mut String, sections: &[CompassSection], ctx: &ReplacementCtx) {
         use std::fmt::Write;
 
-        let tutorials_title = ctx.config.tutorials_title();
-        let tutorials_description = ctx.config.tutorials_description();
-        let tutorials_link = ctx.config.tutorials_link().display();
-        let how_to_guide_title = ctx.config.how_to_guides_title();
-        let how_to_guide_description = ctx.config.how_to_guides_description();
-        let how_to_guides_link = ctx.config.how_to_guides_link().display();
-        let reference_title = ctx.config.reference_title();
-        let reference_description = ctx.config.reference_description();
-        let reference_link = ctx.config.reference_link().display();
-        let explanation_title = ctx.config.explanation_title();
-        let explanation_description = ctx.config.explanation_description();
-        let explanation_link = ctx.config.explanation_link().display();
+        const MARGIN: usize = 30;
+        const GAP: usize = 10;
+        const CARD_WIDTH: usize = 280;
+        const CARD_HEIGHT: usize = 150;
+        const PADDING: usize = 16;
+        const DESCRIPTION_TOP: usize = PADDING + 48;
+        const LINE_HEIGHT: usize = 18;
+        const TEXT_WIDTH: usize = CARD_WIDTH - 2 * PADDING;
+        // Estimated widths of a half-width character in the title and description fonts.
+        const TITLE_CHAR_WIDTH: usize = 11;
+        const DESCRIPTION_CHAR_WIDTH: usize = 8;
+        const MAX_DESCRIPTION_LINES: usize =
+            (CARD_HEIGHT - PADDING - DESCRIPTION_TOP) / LINE_HEIGHT + 1;
+        const WIDTH: usize = 2 * MARGIN + 2 * CARD_WIDTH + GAP;
+        const MAP_HEIGHT: usize = 2 * MARGIN + 2 * CARD_HEIGHT + GAP;
+
+        let (map, extras) = Self::arrange_on_map(sections);
+        let extra_rows = extras.len().div_ceil(2);
+        let height = MAP_HEIGHT + extra_rows * (CARD_HEIGHT + GAP);
+
+        let AxisLabels {
+            action,
+            cognition,
+            acquisition,
+            application,
+        } = ctx.config.axis_labels();
+        let (centre_x, centre_y) = (WIDTH / 2, MAP_HEIGHT / 2);
+        let (right, bottom) = (WIDTH - MARGIN / 2, MAP_HEIGHT - MARGIN / 2);
         writedoc!(
             buf,
             r#"
-                <span>
-                    <div class="quote-grid">
-                        <blockquote>
-                            <p>
-                                <div class="diataxis-card-header">
-                                    <a href="{tutorials_link}">{tutorials_title}</a>
-                                </div>
-                                {tutorials_description}
-                            </p>
-                        </blockquote>
-                        <blockquote>
-                            <p>
-                                <div class="diataxis-card-header">
-                                    <a href="{how_to_guides_link}">{how_to_guide_title}</a>
-                                </div>
-                                {how_to_guide_description}
-                            </p>
-                        </blockquote>
-                        <blockquote>
-                            <p>
-                                <div class="diataxis-card-header">
-                                    <a href="{explanation_link}">{explanation_title}</a>
-                                </div>
-                                {explanation_description}
-                            </p>
-                        </blockquote>
-                        <blockquote>
-                            <p>
-                                <div class="diataxis-card-header">
-                                    <a href="{reference_link}">{reference_title}</a>
-                                </div>
-                                {reference_description}
-                            </p>
-                        </blockquote>
-                    </div>
-                </span>
+                <div class="diataxis-svg-compass">
+                <svg viewBox="0 0 {WIDTH} {height}" xmlns="http://www.w3.org/2000/svg" role="navigation">
+                <g style="fill: var(--fg, #333); font-size: 13px; font-variant: small-caps" text-anchor="middle" dominant-baseline="middle">
+                <text x="{centre_x}" y="{half_margin}">{action}</text>
+                <text x="{centre_x}" y="{bottom}">{cognition}</text>
+                <text transform="translate({half_margin} {centre_y}) rotate(-90)">{acquisition}</text>
+                <text transform="translate({right} {centre_y}) rotate(90)">{application}</text>
+                </g>
             "#,
+            half_margin = MARGIN / 2,
+            action = escape_html(action),
+            cognition = escape_html(cognition),
+            acquisition = escape_html(acquisition),
+            application = escape_html(application),
         )
         .expect("internal error: cannot to write to string");
+        let positions = (0..).map(|index: usize| {
+            let x = MARGIN + (index % 2) * (CARD_WIDTH + GAP);
+            let y = MARGIN + (index / 2) * (CARD_HEIGHT + GAP);
+            (x, y)
+        });
+        let extra_positions = (0..).map(|index: usize| {
+            let x = MARGIN + (index % 2) * (CARD_WIDTH + GAP);
+            let y = MAP_HEIGHT + (index / 2) * (CARD_HEIGHT + GAP);
+            (x, y)
+        });
+        let cards = iter::zip(map, positions)
+            .filter_map(|(section, position)| Some((section?, position)))
+            .chain(iter::zip(extras, extra_positions));
+        for (section, (x, y)) in cards {
+            let CompassSection {
+                title,
+                description,
+                href,
+                active,
+                ..
+            } = section;
+            let (aria_current, stroke_width) = if *active {
+                (r#" aria-current="true""#, 3)
+            } else {
+                ("", 1)
+            };
+            let text_x = x + PADDING;
+            writedoc!(
+                buf,
+                r#"
+                    <a href="{href}"{aria_current}>
+                    <rect x="{x}" y="{y}" width="{CARD_WIDTH}" height="{CARD_HEIGHT}" rx="8" style="fill: var(--quote-bg, #f6f7f6); stroke: var(--quote-border, #d0d7de); stroke-width: {stroke_width}"/>
+                    <text x="{text_x}" y="{title_y}" style="fill: var(--links, #4183c4); font-size: 18px; font-weight: bold">{title}</text>
+                    <text style="fill: var(--fg, #333); font-size: 14px">
+                "#,
+                href = escape_html(href),
+                title_y = y + PADDING + 18,
+                title = escape_html(&fit_line(title, TEXT_WIDTH / TITLE_CHAR_WIDTH)),
+            )
+            .expect("internal error: cannot to write to string");
+            let description = if ctx.config.markdown_descriptions {
+                markdown_to_plain_text(description)
+            } else {
+                description.to_string()
+            };
+            let max_line_width = TEXT_WIDTH / DESCRIPTION_CHAR_WIDTH;
+            let lines = wrap_words(&description, max_line_width, MAX_DESCRIPTION_LINES);
+            for (line_index, line) in lines.iter().enumerate() {
+                writeln!(
+                    buf,
+                    r#"<tspan x="{text_x}" y="{}">{}</tspan>"#,
+                    y + DESCRIPTION_TOP + line_index * LINE_HEIGHT,
+                    escape_html(line),
+                )
+                .expect("internal error: cannot to write to string");
+            }
+            writeln!(buf, "</text>\n</a>").expect("internal error: cannot to write to string");
+        }
+        writeln!(buf, "</svg>\n</div>").expect("internal error: cannot to write to string");
+    }
+
+    /// Places the quadrants' sections at their positions on the Diátaxis map, in reading order,
+    /// leaving gaps for hidden quadrants, and returns the extra cards separately.
+    fn arrange_on_map<'a, 's>(
+        sections: &'a [CompassSection<'s>],
+    ) -> (
+        [Option<&'a CompassSection<'s>>; 4],
+        Vec<&'a CompassSection<'s>>,
+    ) {
+        let map = Quadrant::ALL.map(|quadrant| {
+            sections
+                .iter()
+                .find(|section| section.quadrant == Some(quadrant))
+        });
+        let extras = sections
+            .iter()
+            .filter(|section| section.quadrant.is_none())
+            .collect();
+        (map, extras)
+    }
+
+    /// Writes the card for one section of the compass, indented by `level` steps.
+    fn write_html_card_to(buf: &mut String, section: &CompassSection, level: usize) {
+        let CompassSection {
+            title,
+            description_html,
+            href,
+            active,
+            ..
+        } = section;
+        let attrs = if *active {
+            r#" class="diataxis-card-active" aria-current="true""#
+        } else {
+            ""
+        };
+        let title = escape_html(title);
+        let href = escape_html(href);
+        let card = formatdoc!(
+            r#"
+                <blockquote{attrs}>
+                    <p>
+                        <div class="diataxis-card-header">
+                            <a href="{href}">{title}</a>
+                        </div>
+                        {description_html}
+                    </p>
+                </blockquote>
+            "#
+        );
+        let indent = "    ".repeat(level);
+        for line in card.lines() {
+            buf.push_str(&indent);
+            buf.push_str(line);
+            buf.push('\n');
+        }
+    }
+
+    fn write_markdown_compass_to(
+        &self,
+        buf: &mut String,
+        _args: &CompassArgs,
+        ctx: &ReplacementCtx,
+    ) {
+        use std::fmt::Write;
+
+        for section in ctx.compass_sections() {
+            let CompassSection {
+                title,
+                description,
+                href,
+                ..
+            } = section;
+            let title = escape_markdown(title);
+            if ctx.format == OutputFormat::Text {
+                let description = if ctx.config.markdown_descriptions {
+                    escape_markdown(&markdown_to_plain_text(description))
+                } else {
+                    escape_markdown(description)
+                };
+                writeln!(buf, "- {title}: {description}")
+            } else {
+                let description = if ctx.config.markdown_descriptions {
+                    description.to_owned()
+                } else {
+                    escape_markdown(description)
+                };
+                writeln!(buf, "- **[{title}]({href})**: {description}")
+            }
+            .expect("internal error: cannot to write to string");
+        }
     }
 
-    fn write_toc_to(&self, buf: &mut String, ctx: &ReplacementCtx) {
+    fn write_toc_to(&self, buf: &mut String, args: &TocArgs, ctx: &ReplacementCtx) -> Result<()> {
         let chapter_path = match &ctx.chapter.source_path {
             Some(path) => path,
-            _ => return,
+            _ => return Ok(()),
         };
-        ctx.chapter
-            .sub_items
+        let entries = TocEntry::collect(&ctx.chapter.sub_items, chapter_path, args, ctx.format, 0);
+        if ctx.format == OutputFormat::Html {
+            let data = json!({
+                "items": entries.iter().map(TocEntry::to_json).collect::<Vec<_>>(),
+                "current_page": ctx.current_page_json(),
+            });
+            if let Some(rendered) = ctx.templates.render("toc", &data) {
+                buf.push_str(&rendered?);
+                return Ok(());
+            }
+        }
+        Self::write_toc_entries_to(buf, &entries, 0);
+        Ok(())
+    }
+
+    fn write_toc_entries_to(buf: &mut String, entries: &[TocEntry], level: usize) {
+        use std::fmt::Write;
+
+        let indent = "  ".repeat(level);
+        for entry in entries {
+            let TocEntry {
+                name,
+                href,
+                children,
+            } = entry;
+            if let Some(href) = href {
+                writeln!(buf, "{indent}- [{name}]({href})")
+            } else {
+                writeln!(buf, "{indent}- {name}")
+            }
+            .expect("internal error: cannot to write to string");
+
+            Self::write_toc_entries_to(buf, children, level + 1);
+        }
+    }
+}
+
+/// An entry in a table of contents.
+struct TocEntry {
+    name: String,
+
+    /// The link to the entry's chapter, if it has one and links are shown.
+    href: Option<String>,
+    children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    /// Collects the entries for `items` on the page with source path `chapter_path`.
+    fn collect(
+        items: &[BookItem],
+        chapter_path: &Path,
+        args: &TocArgs,
+        format: OutputFormat,
+        level: usize,
+    ) -> Vec<Self> {
+        if level >= args.depth.get() {
+            return vec![];
+        }
+
+        let mut children = items
             .iter()
             .filter_map(|item| match item {
                 BookItem::Chapter(chapter) => Some(chapter),
                 _ => None,
             })
+            .collect::<Vec<_>>();
+        match args.sort {
+            TocSort::Summary => {}
+            TocSort::Title => children.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name)),
+        }
+        children
+            .into_iter()
             .map(|child| {
-                let link_path = child
+                let href = child
                     .source_path
                     .as_deref()
-                    .map(|path| relative_to(chapter_path, path));
-                (&child.name, link_path)
-            })
-            .map(|(child_name, mut link_path)| {
-                if let Some(link_path) = &mut link_path {
-                    if link_path
-                        .file_name()
-                        .is_some_and(|file_name| file_name == "README.md")
-                    {
-                        link_path.set_file_name("index.html")
-                    }
+                    .filter(|_| format != OutputFormat::Text)
+                    .map(|path| match format {
+                        OutputFormat::Html => Link::to_chapter(path).rendered(),
+                        _ => Link::to_chapter(path),
+                    })
+                    .map(|link| link.href(Some(chapter_path)));
+                Self {
+                    name: child.name.clone(),
+                    href,
+                    children: Self::collect(
+                        &child.sub_items,
+                        chapter_path,
+                        args,
+                        format,
+                        level + 1,
+                    ),
                 }
-                (child_name, link_path)
             })
-            .for_each(|(child_name, link_path)| {
-                use std::fmt::Write;
-                if let Some(link_path) = link_path {
-                    writeln!(buf, "- [{child_name}]({})", link_path.display())
-                        .expect("internal error: cannot to write to string")
-                } else {
-                    writeln!(buf, "- {child_name}")
-                        .expect("internal error: cannot to write to string")
-                }
-            });
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "link": self.href,
+            "children": self.children.iter().map(Self::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Directive {
+    fn write_kind_badge_to(&self, buf: &mut String, args: &KindArgs, ctx: &ReplacementCtx) {
+        use std::fmt::Write;
+
+        let KindArgs { quadrant } = args;
+        match ctx.format {
+            OutputFormat::Html => write!(
+                buf,
+                r#"<span class="diataxis-badge diataxis-badge-{}">{}</span>"#,
+                quadrant.name(),
+                quadrant.badge_text(),
+            ),
+            OutputFormat::Markdown => write!(buf, "**{}**", quadrant.badge_text()),
+            OutputFormat::Text => buf.write_str(quadrant.badge_text()),
+        }
+        .expect("internal error: cannot to write to string");
+    }
+}
+
+/// Splits `text` into at most `max_lines` lines no wider than `max_width`, as measured by
+/// [`text_width`], breaking between words where possible and within words which are too wide for a
+/// line of their own.
+///
+/// If `text` does not fit, the last line ends with an ellipsis.
+fn wrap_words(text: &str, max_width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = text_width(word);
+        if !line.is_empty() {
+            // Words which are too wide for a line of their own start on the current line, if
+            // there is room.
+            let fits = line_width + 1 + word_width <= max_width
+                || (word_width > max_width && line_width + 2 <= max_width);
+            if fits {
+                line.push(' ');
+                line_width += 1;
+            } else {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+        }
+        for c in word.chars() {
+            if !line.is_empty() && line_width + char_width(c) > max_width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += char_width(c);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            *last = ellipsize(last, max_width);
+        }
+    }
+    lines
+}
+
+/// Returns `text` if it fits on one line no wider than `max_width`, and otherwise shortens it to
+/// fit with an ellipsis.
+fn fit_line(text: &str, max_width: usize) -> String {
+    if text_width(text) <= max_width {
+        return text.to_owned();
+    }
+    ellipsize(text, max_width)
+}
+
+/// Shortens `text` as needed to end it with an ellipsis on one line no wider than `max_width`.
+fn ellipsize(text: &str, max_width: usize) -> String {
+    let mut line = String::new();
+    let mut line_width = 0;
+    for c in text.chars() {
+        if line_width + char_width(c) + 1 > max_width {
+            break;
+        }
+        line.push(c);
+        line_width += char_width(c);
+    }
+    line.truncate(line.trim_end().len());
+    line.push('…');
+    line
+}
+
+/// Estimates the width of `text` in columns, in which full-width characters take two columns.
+fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Estimates the width of `c` in columns.
+fn char_width(c: char) -> usize {
+    match c {
+        '\u{1100}'..='\u{115f}'
+        | '\u{2e80}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{20000}'..='\u{3fffd}' => 2,
+        _ => 1,
     }
 }
 
-/// Computes the path of `target` relative to `source`.
+/// Removes `.` components from `path`.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Computes the path of `target` relative to the directory which contains `source`.
 ///
-/// `target` must be a sibling of `source` or be in a child directory which is a sibling of
-/// `source`. Symlinks are not supported.
+/// Both paths must be relative to the same root. Symlinks are not supported.
 fn relative_to(source: &Path, target: &Path) -> PathBuf {
-    target
-        .components()
-        .zip(source.components().chain(iter::repeat(Component::RootDir)))
-        .skip_while(|(target_component, source_component)| target_component == source_component)
-        .map(|(target_component, _)| target_component)
-        .collect::<PathBuf>()
+    fn normal_components(path: &Path) -> Vec<Component<'_>> {
+        path.components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect()
+    }
+    let source_dir_components = normal_components(source.parent().unwrap_or(Path::new("")));
+    let target_components = normal_components(target);
+    let common_prefix_len = source_dir_components
+        .iter()
+        .zip(&target_components)
+        .take_while(|(source_component, target_component)| source_component == target_component)
+        .count();
+    iter::repeat_n(
+        Component::ParentDir,
+        source_dir_components.len() - common_prefix_len,
+    )
+    .chain(target_components[common_prefix_len..].iter().copied())
+    .collect()
 }
 
 struct ReplacementCtx<'ctx> {
-    #[allow(unused)]
     config: &'ctx Config<'ctx>,
-    #[allow(unused)]
+    index: &'ctx BookIndex,
+    templates: &'ctx Templates,
+    format: OutputFormat,
     chapter: &'ctx Chapter,
 }
 
+impl ReplacementCtx<'_> {
+    /// Returns the sections of the compass in order, with links which work from the current
+    /// chapter.
+    fn compass_sections(&self) -> Vec<CompassSection<'_>> {
+        let config = self.config;
+        let active_quadrant = self.active_quadrant();
+        // Generated chapters have no source path, but are placed at their paths.
+        let chapter_path = self
+            .chapter
+            .source_path
+            .as_deref()
+            .or(self.chapter.path.as_deref());
+        config
+            .compass_cards()
+            .into_iter()
+            .map(|card| {
+                let description = config.card_description(card);
+                let description_html = if config.markdown_descriptions {
+                    render_inline_markdown(description)
+                } else {
+                    escape_html(description)
+                };
+                let (key, quadrant) = match card {
+                    CompassCard::Quadrant(quadrant) => {
+                        let key = match quadrant {
+                            Quadrant::Tutorial => "tutorials",
+                            Quadrant::HowToGuide => "how_to_guides",
+                            Quadrant::Explanation => "explanation",
+                            Quadrant::Reference => "reference",
+                        };
+                        (key, Some(quadrant))
+                    }
+                    CompassCard::Extra(name) => (name, None),
+                };
+                CompassSection {
+                    key,
+                    quadrant,
+                    active: quadrant.is_some() && quadrant == active_quadrant,
+                    title: config.card_title(card),
+                    description,
+                    description_html,
+                    href: match self.format {
+                        OutputFormat::Html => config.card_link(card).rendered().href(chapter_path),
+                        OutputFormat::Markdown | OutputFormat::Text => {
+                            config.card_link(card).href(chapter_path)
+                        }
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the quadrant which the current chapter belongs to.
+    ///
+    /// This is the chapter's declared kind if it has one. Otherwise, it is the quadrant whose
+    /// compass link points to the chapter or to its nearest ancestor which any compass link points
+    /// to.
+    fn active_quadrant(&self) -> Option<Quadrant> {
+        self.index
+            .kind(self.chapter)
+            .or_else(|| self.config.located_quadrant(self.index, self.chapter))
+    }
+
+    /// Describes the current chapter to templates.
+    fn current_page_json(&self) -> Value {
+        json!({
+            "name": self.chapter.name,
+            "path": self.chapter.source_path,
+        })
+    }
+}
+
+/// One section of the compass, as shown on a particular page.
+struct CompassSection<'a> {
+    /// The name by which templates refer to this section.
+    key: &'a str,
+
+    /// The quadrant shown by this section, or `None` for extra cards.
+    quadrant: Option<Quadrant>,
+
+    /// Whether the current chapter belongs to this section's quadrant.
+    active: bool,
+    title: &'a str,
+    description: &'a str,
+
+    /// The description as HTML, either escaped or rendered from markdown.
+    description_html: String,
+    href: String,
+}
+
+impl CompassSection<'_> {
+    fn to_json(&self) -> Value {
+        json!({
+            "key": self.key,
+            "title": self.title,
+            "description": self.description,
+            "description_html": self.description_html,
+            "link": self.href,
+            "active": self.active,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use googletest::expect_that;
-    use googletest::matchers::{all, contains_substring};
+    use googletest::matchers::{
+        all, anything, contains_substring, elements_are, eq, err, not, ok, starts_with,
+    };
     use indoc::indoc;
     use insta::assert_snapshot;
     use mdbook::preprocess::CmdPreprocessor;
@@ -486,7 +1317,7 @@ mod tests {
         }
 
         #[googletest::test]
-        fn configured() {
+        fn markdown_renderer() {
             let input_json = indoc! {br##"
                 [{
                     "root": "/path/to/book",
@@ -499,9 +1330,117 @@ mod tests {
                             "title": "TITLE"
                         },
                         "preprocessor": {
-                            "diataxis": {
-                                "compass": {
-                                    "tutorials": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis kind tutorial}}\n\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "guides/chapter_1.md",
+                            "source_path": "guides/chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    not(contains_substring("<")),
+                    contains_substring("**Tutorial**"),
+                    contains_substring("- **[Tutorials](../tutorials/index.md)**"),
+                )
+            );
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn markdown_renderer_escaped() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "order": ["tutorials"],
+                                    "tutorials": {
+                                        "title": "Lessons [b]",
+                                        "description": "<script>x</script> *not* emphasis"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "epub",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq(
+                    r"- **[Lessons \[b\]](tutorials/index.md)**: \<script\>x\</script\> \*not\* emphasis
+"
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn configured() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "tutorials": {
                                         "title": "custom-tutorials-title",
                                         "description": "custom-tutorials-description",
                                         "link": "custom-tutorials/README.md"
@@ -567,13 +1506,9 @@ mod tests {
             );
             assert_snapshot!(chapter.content);
         }
-    }
-
-    mod toc {
-        use super::*;
 
         #[googletest::test]
-        fn default() {
+        fn external_and_fragment_links() {
             let input_json = indoc! {br##"
                 [{
                     "root": "/path/to/book",
@@ -586,7 +1521,19 @@ mod tests {
                             "title": "TITLE"
                         },
                         "preprocessor": {
-                            "diataxis": {}
+                            "diataxis": {
+                                "compass": {
+                                    "how-to-guides": {
+                                        "link": "#how-to"
+                                    },
+                                    "reference": {
+                                        "link": "https://example.com/api"
+                                    },
+                                    "explanation": {
+                                        "link": "explanations/README.md#background"
+                                    }
+                                }
+                            }
                         }
                     },
                     "renderer": "html",
@@ -595,40 +1542,129 @@ mod tests {
                     "sections": [{
                         "Chapter": {
                             "name": "Chapter 1",
-                            "content": "# Chapter 1\n{{#diataxis table-of-contents}}",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
                             "number": [1],
-                            "sub_items": [{
-                                "Chapter": {
-                                    "name": "Non-draft sub-chapter",
-                                    "content": "non-draft sub content",
-                                    "number": [1, 1],
-                                    "sub_items": [],
-                                    "path": "chapter_1/dir/non_draft_sub.md",
-                                    "source_path": "chapter_1/dir/non_draft_sub.md",
-                                    "parent_names": []
-                                }
-                            }, {
-                                "Chapter": {
-                                    "name": "Draft sub-chapter",
-                                    "content": "draft sub content",
-                                    "number": [1, 1],
-                                    "sub_items": [],
-                                    "path": "chapter_1/dir/draft_sub.md",
-                                    "parent_names": []
+                            "sub_items": [],
+                            "path": "guide/chapter_1.md",
+                            "source_path": "guide/chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring(r#"href="../tutorials/index.html""#),
+                    contains_substring(r##"href="#how-to""##),
+                    contains_substring(r#"href="https://example.com/api""#),
+                    contains_substring(r#"href="../explanations/index.html#background""#),
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn escaped() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "tutorials": {
+                                        "title": "Tips & tricks",
+                                        "description": "Learn <b>*everything*</b>"
+                                    }
                                 }
-                            }, {
-                                "Chapter": {
-                                    "name": "README sub-chapter",
-                                    "content": "README sub content",
-                                    "number": [1, 1],
-                                    "sub_items": [],
-                                    "path": "chapter_1/sub_chapter/README.md",
-                                    "source_path": "chapter_1/sub_chapter/README.md",
-                                    "parent_names": []
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring(">Tips &amp; tricks</a>"),
+                    contains_substring("Learn &lt;b&gt;*everything*&lt;/b&gt;"),
+                    not(contains_substring("<b>")),
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn markdown_descriptions() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "descriptions": "markdown",
+                                    "tutorials": {
+                                        "description": "Learn *everything* with `mdbook` & <b>more</b>"
+                                    },
+                                    "reference": {
+                                        "description": "See [the API](https://example.com/api)"
+                                    }
                                 }
-                            }],
-                            "path": "chapter_1/README.md",
-                            "source_path": "chapter_1/README.md",
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
                             "parent_names": []
                         }
                     }],
@@ -644,11 +1680,1903 @@ mod tests {
             expect_that!(
                 chapter.content,
                 all!(
-                    contains_substring("- [Non-draft sub-chapter](dir/non_draft_sub.md)"),
-                    contains_substring("- Draft sub-chapter"),
+                    contains_substring(
+                        "Learn <em>everything</em> with <code>mdbook</code> &amp; &lt;b&gt;more&lt;/b&gt;"
+                    ),
+                    contains_substring(r#"See <a href="https://example.com/api">the API</a>"#),
                 )
             );
-            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn translated() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "fr",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "tutorials": {
+                                        "title": "Premiers pas"
+                                    }
+                                },
+                                "translations": {
+                                    "fr": {
+                                        "reference": {
+                                            "description": "Tout sur l'API"
+                                        }
+                                    },
+                                    "eo": {
+                                        "reference": {
+                                            "title": "Referenco"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn per_language() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "fr-CA",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "tutorials": {
+                                        "title": "Learn",
+                                        "link": "en/tutorials.md",
+                                        "lang": {
+                                            "fr": {
+                                                "title": "Apprendre",
+                                                "link": "fr/tutoriels.md"
+                                            }
+                                        }
+                                    },
+                                    "how-to-guides": {
+                                        "lang": {
+                                            "de": {
+                                                "title": "Anleitungen"
+                                            }
+                                        }
+                                    },
+                                    "reference": {
+                                        "lang": {
+                                            "fr": {
+                                                "description": "Informations techniques"
+                                            },
+                                            "fr-CA": {
+                                                "description": "Renseignements techniques"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn active_card() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "# Tutorials",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "First steps",
+                                    "content": "# First steps\n{{#diataxis compass}}",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "tutorials/first-steps.md",
+                                    "source_path": "tutorials/first-steps.md",
+                                    "parent_names": ["Tutorials"]
+                                }
+                            }],
+                            "path": "tutorials/index.md",
+                            "source_path": "tutorials/index.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Design",
+                            "content": "# Design\n{{#diataxis kind explanation}}\n{{#diataxis compass}}",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "tutorials/design.md",
+                            "source_path": "tutorials/design.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Elsewhere",
+                            "content": "# Elsewhere\n{{#diataxis compass}}",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "elsewhere.md",
+                            "source_path": "elsewhere.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapters = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let active_card = |quadrant_href: &str| {
+                contains_substring(format!(
+                    "<blockquote class=\"diataxis-card-active\" aria-current=\"true\">\n            <p>\n                <div class=\"diataxis-card-header\">\n                    <a href=\"{quadrant_href}\">"
+                ))
+            };
+            expect_that!(chapters[1].content, active_card("index.html"));
+            expect_that!(
+                chapters[2].content,
+                all!(
+                    active_card("../explanations/index.html"),
+                    not(active_card("index.html")),
+                )
+            );
+            expect_that!(
+                chapters[3].content,
+                not(contains_substring("diataxis-card-active"))
+            );
+        }
+
+        #[googletest::test]
+        fn axes_layout() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "layout": "axes"
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}\n{{#diataxis compass layout=grid}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(chapter.content.matches("diataxis-axes-grid").count(), eq(1));
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn svg_layout() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "reference": {
+                                        "title": "APIs & CLIs"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis kind how-to-guide}}\n{{#diataxis compass layout=svg}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring("<svg "),
+                    contains_substring(r#"<a href="how-to/index.html" aria-current="true">"#),
+                    contains_substring("APIs &amp; CLIs"),
+                    not(contains_substring("\n\n<")),
+                )
+            );
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn svg_long_markdown_description() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "descriptions": "markdown",
+                                    "layout": "svg",
+                                    "tutorials": {
+                                        "description": "Hands-on **lessons** which take you through [building a book](https://example.com) from an empty directory to a published site, one small step at a time, with plenty of checkpoints along the way"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            let tutorials_card = chapter
+                .content
+                .split("</a>")
+                .find(|card| card.contains("Tutorials"))
+                .unwrap();
+            expect_that!(
+                tutorials_card,
+                all!(
+                    contains_substring("Hands-on lessons which take"),
+                    contains_substring("…</tspan>"),
+                    not(contains_substring("**")),
+                    not(contains_substring("](")),
+                )
+            );
+            expect_that!(tutorials_card.matches("<tspan").count(), eq(4));
+        }
+
+        #[googletest::test]
+        fn svg_long_words() {
+            expect_that!(
+                wrap_words("see https://example.com/a/very/long/path", 16, 4),
+                elements_are![
+                    eq("see https://exam"),
+                    eq("ple.com/a/very/l"),
+                    eq("ong/path"),
+                ]
+            );
+            expect_that!(
+                wrap_words("手を動かして学ぶレッスン", 10, 3),
+                elements_are![eq("手を動かし"), eq("て学ぶレッ"), eq("スン")]
+            );
+            expect_that!(
+                wrap_words("手を動かして学ぶレッスン", 10, 2),
+                elements_are![eq("手を動かし"), eq("て学ぶレ…")]
+            );
+            expect_that!(
+                fit_line("A title which is much too long", 16),
+                eq("A title which i…")
+            );
+            expect_that!(fit_line("Short title", 16), eq("Short title"));
+        }
+
+        #[googletest::test]
+        fn extra_cards() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "order": ["how-to-guides", "reference", "faq"],
+                                    "cards": {
+                                        "faq": {
+                                            "title": "FAQ",
+                                            "description": "Answers to common questions",
+                                            "link": "faq.md"
+                                        },
+                                        "notes": {
+                                            "title": "Release notes",
+                                            "chapter": "Release notes"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "FAQ",
+                            "content": "# FAQ",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "faq.md",
+                            "source_path": "faq.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Release notes",
+                            "content": "# Release notes",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "release-notes.md",
+                            "source_path": "release-notes.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn hidden_quadrant_on_map() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "order": ["how-to-guides", "reference", "faq"],
+                                    "cards": {
+                                        "faq": {
+                                            "title": "FAQ",
+                                            "description": "Answers to common questions",
+                                            "link": "faq.md"
+                                        },
+                                        "notes": {
+                                            "title": "Release notes",
+                                            "chapter": "Release notes"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass layout=axes}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "FAQ",
+                            "content": "# FAQ",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "faq.md",
+                            "source_path": "faq.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Release notes",
+                            "content": "# Release notes",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "release-notes.md",
+                            "source_path": "release-notes.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring(r#"<div class="diataxis-card-placeholder"></div>"#),
+                    contains_substring(r#"<a href="faq.html">FAQ</a>"#),
+                    not(contains_substring("Tutorials")),
+                    not(contains_substring("Release notes")),
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn unknown_card_in_order() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "order": ["tutorials", "how-to-guide"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "FAQ",
+                            "content": "# FAQ",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "faq.md",
+                            "source_path": "faq.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Release notes",
+                            "content": "# Release notes",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "release-notes.md",
+                            "source_path": "release-notes.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let result = DiataxisPreprocessor::new().run(&ctx, book);
+            expect_that!(
+                result.map_err(|err| err.to_string()),
+                err(eq(
+                    "unknown compass card `how-to-guide` in `compass.order`, did you mean `how-to-guides`?"
+                ))
+            );
+        }
+
+        #[googletest::test]
+        fn hidden_card_with_missing_chapter() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "order": ["tutorials"],
+                                    "cards": {
+                                        "faq": {
+                                            "title": "FAQ",
+                                            "chapter": "Frequently asked questions"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring("Tutorials"),
+                    not(contains_substring("FAQ")),
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn nested_chapter() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "how-to-guides": {
+                                        "link": "guides/README.md"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "guides/intro.md",
+                            "source_path": "guides/intro.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring(r#"href="../tutorials/index.html""#),
+                    contains_substring(r#"href="index.html""#),
+                    contains_substring(r#"href="../explanations/index.html""#),
+                    contains_substring(r#"href="../reference-materials/index.html""#),
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn chapter_links() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "reference": {
+                                        "chapter": "API reference"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "guides/intro.md",
+                            "source_path": "guides/intro.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "API reference",
+                            "content": "# API reference",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "api/README.md",
+                            "source_path": "api/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                contains_substring(r#"<a href="../api/index.html">Reference</a>"#)
+            );
+        }
+
+        #[googletest::test]
+        fn missing_chapter() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "compass": {
+                                    "reference": {
+                                        "chapter": "API reference"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "intro.md",
+                            "source_path": "intro.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let err = DiataxisPreprocessor::new().run(&ctx, book).unwrap_err();
+            expect_that!(
+                err.to_string(),
+                eq("cannot find chapter `API reference` named in `compass.reference.chapter`")
+            );
+        }
+    }
+
+    mod toc {
+        use super::*;
+
+        #[googletest::test]
+        fn default() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Non-draft sub-chapter",
+                                    "content": "non-draft sub content",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/dir/non_draft_sub.md",
+                                    "source_path": "chapter_1/dir/non_draft_sub.md",
+                                    "parent_names": []
+                                }
+                            }, {
+                                "Chapter": {
+                                    "name": "Draft sub-chapter",
+                                    "content": "draft sub content",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/dir/draft_sub.md",
+                                    "parent_names": []
+                                }
+                            }, {
+                                "Chapter": {
+                                    "name": "README sub-chapter",
+                                    "content": "README sub content",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/sub_chapter/README.md",
+                                    "source_path": "chapter_1/sub_chapter/README.md",
+                                    "parent_names": []
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring("- [Non-draft sub-chapter](dir/non_draft_sub.html)"),
+                    contains_substring("- Draft sub-chapter"),
+                )
+            );
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn sorted_by_title() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis table-of-contents sort=title}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Zebras",
+                                    "content": "zebra content",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/zebras.md",
+                                    "source_path": "chapter_1/zebras.md",
+                                    "parent_names": []
+                                }
+                            }, {
+                                "Chapter": {
+                                    "name": "Aardvarks",
+                                    "content": "aardvark content",
+                                    "number": [1, 2],
+                                    "sub_items": [],
+                                    "path": "chapter_1/aardvarks.md",
+                                    "source_path": "chapter_1/aardvarks.md",
+                                    "parent_names": []
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq(indoc! {"
+                    # Chapter 1
+                    - [Aardvarks](aardvarks.html)
+                    - [Zebras](zebras.html)
+                "})
+            );
+        }
+
+        #[googletest::test]
+        fn nested() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis table-of-contents depth=2}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Section",
+                                    "content": "section content",
+                                    "number": [1, 1],
+                                    "sub_items": [{
+                                        "Chapter": {
+                                            "name": "Subsection",
+                                            "content": "subsection content",
+                                            "number": [1, 1, 1],
+                                            "sub_items": [{
+                                                "Chapter": {
+                                                    "name": "Too deep",
+                                                    "content": "too deep content",
+                                                    "number": [1, 1, 1, 1],
+                                                    "sub_items": [],
+                                                    "path": "chapter_1/section/subsection/too_deep.md",
+                                                    "source_path": "chapter_1/section/subsection/too_deep.md",
+                                                    "parent_names": []
+                                                }
+                                            }],
+                                            "path": "chapter_1/section/subsection/README.md",
+                                            "source_path": "chapter_1/section/subsection/README.md",
+                                            "parent_names": []
+                                        }
+                                    }, {
+                                        "Chapter": {
+                                            "name": "Elsewhere",
+                                            "content": "elsewhere content",
+                                            "number": [1, 1, 2],
+                                            "sub_items": [],
+                                            "path": "elsewhere.md",
+                                            "source_path": "elsewhere.md",
+                                            "parent_names": []
+                                        }
+                                    }],
+                                    "path": "chapter_1/section/README.md",
+                                    "source_path": "chapter_1/section/README.md",
+                                    "parent_names": []
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    contains_substring("- [Section](section/index.html)"),
+                    contains_substring("  - [Subsection](section/subsection/index.html)"),
+                    contains_substring("  - [Elsewhere](../elsewhere.html)"),
+                    not(contains_substring("Too deep")),
+                )
+            );
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn markdown_renderer() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Child",
+                                    "content": "# Child",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "sub/README.md",
+                                    "source_path": "sub/README.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }, {
+                                "Chapter": {
+                                    "name": "Other",
+                                    "content": "# Other",
+                                    "number": [1, 2],
+                                    "sub_items": [],
+                                    "path": "other.md",
+                                    "source_path": "other.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq("# Chapter 1\n- [Child](sub/index.md)\n- [Other](other.md)\n")
+            );
+        }
+    }
+
+    mod kind {
+        use super::*;
+
+        #[googletest::test]
+        fn badge() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "{{#diataxis kind how-to-guide}}\n# Chapter 1",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq(indoc! {r#"
+                    <span class="diataxis-badge diataxis-badge-how-to-guide">How-to guide</span>
+                    # Chapter 1"#})
+            );
+        }
+
+        #[googletest::test]
+        fn index() {
+            let input_json = indoc! {br##"
+                {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "{{#diataxis kind tutorial}}\n# Chapter 1",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Sub-chapter",
+                                    "content": "{{#diataxis kind reference}}\n{{#diataxis kind explanation}}",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/sub_chapter.md",
+                                    "source_path": "chapter_1/sub_chapter.md",
+                                    "parent_names": []
+                                }
+                            }, {
+                                "Chapter": {
+                                    "name": "Undeclared",
+                                    "content": "no kind here",
+                                    "number": [1, 2],
+                                    "sub_items": [],
+                                    "path": "chapter_1/undeclared.md",
+                                    "source_path": "chapter_1/undeclared.md",
+                                    "parent_names": []
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }
+            "##};
+            let book: Book = serde_json::from_slice(&input_json[..]).unwrap();
+            let mut diagnostics = vec![];
+            let index = BookIndex::new(&book, &KindPatterns::default(), &mut diagnostics);
+            let kinds = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => {
+                        Some((chapter.name.as_str(), index.kind(chapter)))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            expect_that!(
+                kinds,
+                eq(&[
+                    ("Chapter 1", Some(Quadrant::Tutorial)),
+                    ("Sub-chapter", Some(Quadrant::Reference)),
+                    ("Undeclared", None),
+                ])
+            );
+            expect_that!(
+                diagnostics
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                elements_are![starts_with(
+                    "chapter_1/sub_chapter.md:2:1: conflicting kinds `reference` and `explanation` declared"
+                )]
+            );
+        }
+
+        #[googletest::test]
+        fn inferred() {
+            let kind_patterns = indoc! {r#"
+                tutorial = ["tutorials/**"]
+                reference = ["api/**", "cli/*.md"]
+                explanation = ["cli/concepts.md"]
+                unclassified = "warn"
+            "#}
+            .parse::<toml::Value>()
+            .unwrap();
+            let kind_patterns = KindPatterns::new(&kind_patterns).unwrap();
+            let input_json = indoc! {br##"
+                {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Tutorial",
+                            "content": "",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "tutorials/first/steps.md",
+                            "source_path": "tutorials/first/steps.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "API",
+                            "content": "",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "api/README.md",
+                            "source_path": "api/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "API design",
+                            "content": "{{#diataxis kind explanation}}",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "api/design.md",
+                            "source_path": "api/design.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "CLI concepts",
+                            "content": "",
+                            "number": [4],
+                            "sub_items": [],
+                            "path": "cli/concepts.md",
+                            "source_path": "cli/concepts.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "CLI internals",
+                            "content": "",
+                            "number": [5],
+                            "sub_items": [],
+                            "path": "cli/internals/parser.md",
+                            "source_path": "cli/internals/parser.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }
+            "##};
+            let book: Book = serde_json::from_slice(&input_json[..]).unwrap();
+            let mut diagnostics = vec![];
+            let index = BookIndex::new(&book, &kind_patterns, &mut diagnostics);
+            let kinds = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => {
+                        Some((chapter.name.as_str(), index.kind(chapter)))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            expect_that!(
+                kinds,
+                eq(&[
+                    ("Tutorial", Some(Quadrant::Tutorial)),
+                    ("API", Some(Quadrant::Reference)),
+                    ("API design", Some(Quadrant::Explanation)),
+                    ("CLI concepts", None),
+                    ("CLI internals", None),
+                ])
+            );
+            expect_that!(
+                diagnostics
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                elements_are![starts_with(
+                    "cli/concepts.md: page matches patterns for both `explanation` and `reference`"
+                )]
+            );
+            expect_that!(
+                index.unclassified,
+                elements_are![eq(Path::new("cli/internals/parser.md"))]
+            );
+        }
+
+        #[googletest::test]
+        fn unknown_pattern_kind() {
+            let kind_patterns = indoc! {r#"
+                tutorials = ["tutorials/**"]
+            "#}
+            .parse::<toml::Value>()
+            .unwrap();
+            expect_that!(
+                KindPatterns::new(&kind_patterns).map_err(|err| err.to_string()),
+                err(eq("unknown kind `tutorials`, did you mean `tutorial`?"))
+            );
+        }
+    }
+
+    mod strict {
+        use super::*;
+
+        #[googletest::test]
+        fn rejects_malformed() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "strict": true
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compas}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Sub-chapter",
+                                    "content": "{{#diataxis table-of-contents depth=two}}",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "chapter_1/sub_chapter.md",
+                                    "source_path": "chapter_1/sub_chapter.md",
+                                    "parent_names": []
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let err = DiataxisPreprocessor::new().run(&ctx, book).unwrap_err();
+            assert_snapshot!(err.to_string());
+        }
+
+        #[googletest::test]
+        fn accepts_well_formed() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "strict": true
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "# Tutorials",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "tutorials/index.md",
+                            "source_path": "tutorials/index.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "How-to guides",
+                            "content": "# How-to guides",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "how-to/index.md",
+                            "source_path": "how-to/index.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Explanation",
+                            "content": "# Explanation",
+                            "number": [4],
+                            "sub_items": [],
+                            "path": "explanations/index.md",
+                            "source_path": "explanations/index.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Reference",
+                            "content": "# Reference",
+                            "number": [5],
+                            "sub_items": [],
+                            "path": "reference-materials/index.md",
+                            "source_path": "reference-materials/index.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            expect_that!(DiataxisPreprocessor::new().run(&ctx, book), ok(anything()));
+        }
+    }
+
+    mod links {
+        use super::*;
+
+        #[googletest::test]
+        fn missing_targets() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "strict": true,
+                                "compass": {
+                                    "tutorials": {
+                                        "link": "tutorial/README.md"
+                                    },
+                                    "how-to-guides": {
+                                        "link": "how-to/README.md#top"
+                                    },
+                                    "explanation": {
+                                        "link": "https://example.com/explanation"
+                                    },
+                                    "reference": {
+                                        "link": "reference.md"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "README.md",
+                            "source_path": "README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "# Tutorials",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "tutorials/README.md",
+                            "source_path": "tutorials/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "How-to guides",
+                            "content": "# How-to guides",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "how-to/README.md",
+                            "source_path": "how-to/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let err = DiataxisPreprocessor::new().run(&ctx, book).unwrap_err();
+            assert_snapshot!(err.to_string());
+        }
+    }
+
+    mod escaping {
+        use super::*;
+
+        #[googletest::test]
+        fn code_and_escapes_left_literal() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "strict": true
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n```\n{{#diataxis compas}}\n```\nUse `{{#diataxis compass}}` or \\{{#diataxis table-of-contents}}.",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq(indoc! {"
+                    # Chapter 1
+                    ```
+                    {{#diataxis compas}}
+                    ```
+                    Use `{{#diataxis compass}}` or {{#diataxis table-of-contents}}."})
+            );
+        }
+    }
+
+    mod malformed {
+        use super::*;
+
+        #[googletest::test]
+        fn left_unchanged() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compas}}\n{{#diataxis table-of-contents depth=two}}\n{{#diataxis",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                eq("# Chapter 1\n{{#diataxis compas}}\n{{#diataxis table-of-contents depth=two}}\n{{#diataxis")
+            );
+        }
+    }
+
+    mod templates {
+        use super::*;
+
+        #[googletest::test]
+        fn custom() {
+            let root = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(root.path().join("theme/diataxis")).unwrap();
+            std::fs::write(
+                root.path().join("theme/diataxis/compass.hbs"),
+                indoc! {r#"
+                    <nav class="cards" data-page="{{current_page.name}}">
+                    {{#each sections}}
+                      <a class="card" href="{{link}}"><h3>{{title}}</h3>{{description}}</a>
+                    {{/each}}
+                    </nav>
+                    <a href="{{reference.link}}">{{reference.title}}</a>
+                "#},
+            )
+            .unwrap();
+            std::fs::write(
+                root.path().join("theme/diataxis/toc.hbs"),
+                indoc! {r#"
+                    <ul>{{#each items}}<li><a href="{{link}}">{{name}}</a>{{#each children}} &gt; {{name}}{{/each}}</li>{{/each}}</ul>
+                "#},
+            )
+            .unwrap();
+
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "templates": {
+                                    "compass": "theme/diataxis/compass.hbs",
+                                    "toc": "theme/diataxis/toc.hbs"
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": "# Chapter 1\n{{#diataxis compass}}\n{{#diataxis table-of-contents depth=2}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Chapter 1.1",
+                                    "content": "",
+                                    "number": [1, 1],
+                                    "sub_items": [{
+                                        "Chapter": {
+                                            "name": "Chapter 1.1.1",
+                                            "content": "",
+                                            "number": [1, 1, 1],
+                                            "sub_items": [],
+                                            "path": "chapter_1/section_1/part_1.md",
+                                            "source_path": "chapter_1/section_1/part_1.md",
+                                            "parent_names": ["Chapter 1", "Chapter 1.1"]
+                                        }
+                                    }],
+                                    "path": "chapter_1/section_1/README.md",
+                                    "source_path": "chapter_1/section_1/README.md",
+                                    "parent_names": ["Chapter 1"]
+                                }
+                            }],
+                            "path": "chapter_1/README.md",
+                            "source_path": "chapter_1/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (mut ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            ctx.root = root.path().to_owned();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all!(
+                    not(contains_substring("blockquote")),
+                    contains_substring(r#"<a class="card" href="../tutorials/index.html">"#),
+                    contains_substring(
+                        r#"<a href="section_1/index.html">Chapter 1.1</a> &gt; Chapter 1.1.1"#
+                    ),
+                )
+            );
+            assert_snapshot!(chapter.content);
+        }
+
+        #[googletest::test]
+        fn missing() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "templates": {
+                                    "compass": "theme/diataxis/compass.hbs"
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let result = DiataxisPreprocessor::new().run(&ctx, book);
+            expect_that!(
+                result.map_err(|err| err.to_string()),
+                err(starts_with("cannot load `compass` template"))
+            );
         }
     }
 }
@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use crate::relative_to;
+
+/// The target of a configured link.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Link {
+    /// A URL outside of the book, which is used as-is.
+    External(String),
+
+    /// A page in the book.
+    Internal {
+        /// The path of the rendered page, relative to the book's source directory.
+        path: PathBuf,
+
+        fragment: Option<String>,
+    },
+}
+
+impl Link {
+    /// Parses a link as written in `book.toml`.
+    pub(crate) fn parse(raw: &str) -> Self {
+        if has_scheme(raw) || raw.starts_with("//") {
+            return Self::External(raw.to_owned());
+        }
+
+        let (path, fragment) = match raw.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment.to_owned())),
+            None => (raw, None),
+        };
+        let path = match Path::new(path) {
+            path if path.as_os_str().is_empty() => PathBuf::new(),
+            path => path.to_owned(),
+        };
+        Self::Internal { path, fragment }
+    }
+
+    /// Creates a link to the chapter whose markdown source is at `path`, relative to the book's
+    /// source directory.
+    pub(crate) fn to_chapter(path: &Path) -> Self {
+        Self::internal(path)
+    }
+
+    /// Returns the link to use in HTML output, in which links to markdown files in the book are
+    /// converted to links to their rendered pages.
+    pub(crate) fn rendered(&self) -> Self {
+        match self {
+            Self::Internal { path, fragment } if !path.as_os_str().is_empty() => Self::Internal {
+                path: rendered_path(path),
+                fragment: fragment.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Creates a link to the page at `path`, relative to the book's source directory.
+    pub(crate) fn internal(path: impl Into<PathBuf>) -> Self {
+        Self::Internal {
+            path: path.into(),
+            fragment: None,
+        }
+    }
+
+    /// Returns the `href` to use for this link on the page with source path `chapter_path`.
+    ///
+    /// Links to `README.md` files are given as links to the `index.md` files which mdBook
+    /// renames them to.
+    pub(crate) fn href(&self, chapter_path: Option<&Path>) -> String {
+        match self {
+            Self::External(url) => url.clone(),
+            Self::Internal { path, fragment } => {
+                let mut href = if path.as_os_str().is_empty() {
+                    String::new()
+                } else {
+                    let path = match path.file_name() {
+                        Some(file_name) if file_name == "README.md" => {
+                            path.with_file_name("index.md")
+                        }
+                        _ => path.clone(),
+                    };
+                    match chapter_path {
+                        Some(chapter_path) => relative_to(chapter_path, &path),
+                        None => path,
+                    }
+                    .display()
+                    .to_string()
+                };
+                if let Some(fragment) = fragment {
+                    href.push('#');
+                    href.push_str(fragment);
+                }
+                href
+            }
+        }
+    }
+}
+
+/// Returns the path of the page rendered from the markdown file at `path`.
+fn rendered_path(path: &Path) -> PathBuf {
+    if path
+        .file_name()
+        .is_some_and(|file_name| file_name == "README.md")
+    {
+        return path.with_file_name("index.html");
+    }
+    path.with_extension("html")
+}
+
+/// Returns whether `raw` starts with a URL scheme such as `https:`.
+///
+/// Single-letter schemes are not recognised, so that Windows drive letters are not mistaken for
+/// them.
+fn has_scheme(raw: &str) -> bool {
+    let Some((scheme, _)) = raw.split_once(':') else {
+        return false;
+    };
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::eq;
+
+    #[googletest::test]
+    fn parse() {
+        expect_that!(
+            Link::parse("https://example.com/api"),
+            eq(&Link::External("https://example.com/api".into()))
+        );
+        expect_that!(
+            Link::parse("mailto:docs@example.com"),
+            eq(&Link::External("mailto:docs@example.com".into()))
+        );
+        expect_that!(
+            Link::parse("reference/index.md#cli"),
+            eq(&Link::Internal {
+                path: "reference/index.md".into(),
+                fragment: Some("cli".into()),
+            })
+        );
+        expect_that!(
+            Link::parse("guides/README.md"),
+            eq(&Link::internal("guides/README.md"))
+        );
+        expect_that!(
+            Link::parse("#top"),
+            eq(&Link::Internal {
+                path: PathBuf::new(),
+                fragment: Some("top".into()),
+            })
+        );
+    }
+
+    #[googletest::test]
+    fn href() {
+        let chapter_path = Some(Path::new("guides/intro.md"));
+        expect_that!(
+            Link::parse("https://example.com/api").href(chapter_path),
+            eq("https://example.com/api")
+        );
+        expect_that!(
+            Link::parse("reference/index.md#cli").href(chapter_path),
+            eq("../reference/index.md#cli")
+        );
+        expect_that!(
+            Link::parse("reference/index.md#cli")
+                .rendered()
+                .href(chapter_path),
+            eq("../reference/index.html#cli")
+        );
+        expect_that!(
+            Link::parse("guides/README.md").href(chapter_path),
+            eq("index.md")
+        );
+        expect_that!(
+            Link::parse("guides/README.md")
+                .rendered()
+                .href(chapter_path),
+            eq("index.html")
+        );
+        expect_that!(Link::parse("#top").href(chapter_path), eq("#top"));
+    }
+}
@@ -0,0 +1,106 @@
+use pulldown_cmark::{html, Event, Parser, Tag, TagEnd};
+
+/// Escapes the characters of `text` which are special in HTML.
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes the characters of `text` which are special in inline markdown, so that it is shown as
+/// written.
+pub(crate) fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Renders `text` as inline markdown, so that it can be placed inside another HTML element.
+///
+/// Raw HTML in `text` is escaped rather than passed through.
+pub(crate) fn render_inline_markdown(text: &str) -> String {
+    let events = Parser::new(text).filter_map(|event| match event {
+        Event::Start(Tag::Paragraph) | Event::End(TagEnd::Paragraph) => None,
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        event => Some(event),
+    });
+    let mut rendered = String::with_capacity(text.len());
+    html::push_html(&mut rendered, events);
+    rendered.trim_end().to_owned()
+}
+
+/// Renders `text` as inline markdown and returns only its text, for places where markup cannot be
+/// shown.
+pub(crate) fn markdown_to_plain_text(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    for event in Parser::new(text) {
+        match event {
+            Event::Text(text) | Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                plain.push_str(&text)
+            }
+            Event::SoftBreak | Event::HardBreak => plain.push(' '),
+            _ => {}
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::eq;
+
+    #[googletest::test]
+    fn escape() {
+        expect_that!(
+            escape_html(r#"<b>Tips & "tricks"</b>"#),
+            eq("&lt;b&gt;Tips &amp; &quot;tricks&quot;&lt;/b&gt;")
+        );
+    }
+
+    #[googletest::test]
+    fn escape_markdown_specials() {
+        expect_that!(
+            escape_markdown(r"[Draft] *new* <b> \ `x`"),
+            eq(r"\[Draft\] \*new\* \<b\> \\ \`x\`")
+        );
+    }
+
+    #[googletest::test]
+    fn inline_markdown() {
+        expect_that!(
+            render_inline_markdown("Use *emphasis*, `code` and [links](https://example.com)"),
+            eq(
+                r#"Use <em>emphasis</em>, <code>code</code> and <a href="https://example.com">links</a>"#
+            )
+        );
+        expect_that!(
+            render_inline_markdown("<script>alert(1)</script> & more"),
+            eq("&lt;script&gt;alert(1)&lt;/script&gt; &amp; more")
+        );
+    }
+
+    #[googletest::test]
+    fn plain_text() {
+        expect_that!(
+            markdown_to_plain_text("Use **bold**, `code` and [links](https://example.com)"),
+            eq("Use bold, code and links")
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::{self, Display};
+
+/// One of the four kinds of documentation described by Diátaxis.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Quadrant {
+    Tutorial,
+    HowToGuide,
+    Explanation,
+    Reference,
+}
+
+impl Quadrant {
+    pub(crate) const ALL: [Self; 4] = [
+        Self::Tutorial,
+        Self::HowToGuide,
+        Self::Explanation,
+        Self::Reference,
+    ];
+
+    /// The name used to declare a page's kind.
+    pub(crate) const fn name(&self) -> &'static str {
+        match self {
+            Self::Tutorial => "tutorial",
+            Self::HowToGuide => "how-to-guide",
+            Self::Explanation => "explanation",
+            Self::Reference => "reference",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|quadrant| quadrant.name() == name)
+    }
+
+    /// The name of this quadrant's section of the compass in `book.toml`.
+    pub(crate) const fn compass_section(&self) -> &'static str {
+        match self {
+            Self::Tutorial => "tutorials",
+            Self::HowToGuide => "how-to-guides",
+            Self::Explanation => "explanation",
+            Self::Reference => "reference",
+        }
+    }
+
+    /// The text shown on the badge of a page of this kind.
+    pub(crate) const fn badge_text(&self) -> &'static str {
+        match self {
+            Self::Tutorial => "Tutorial",
+            Self::HowToGuide => "How-to guide",
+            Self::Explanation => "Explanation",
+            Self::Reference => "Reference",
+        }
+    }
+}
+
+impl Display for Quadrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,1069 @@
+use mdbook::book::{Book, Chapter};
+use mdbook::BookItem;
+
+use crate::config::{AutoCompass, AutoCompassPosition, CompassCard, Config};
+use crate::diagnostic::Diagnostic;
+use crate::directive::{find_directives, Directive};
+use crate::link::Link;
+use crate::markup::{escape_html, escape_markdown};
+use crate::{normalize, BookIndex, OutputFormat};
+
+/// Adds a table of contents to each chapter which has sub-chapters but whose content is empty or
+/// consists only of headings and directives.
+pub(crate) fn insert_auto_tocs(book: &mut Book) {
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        if chapter.path.is_none() {
+            return;
+        }
+        let has_children = chapter
+            .sub_items
+            .iter()
+            .any(|sub_item| matches!(sub_item, BookItem::Chapter(_)));
+        let heading_only = chapter.content.lines().all(|line| {
+            line.trim().is_empty() || line.trim_start().starts_with('#') || is_directive_line(line)
+        });
+        if !has_children || !heading_only {
+            return;
+        }
+        let has_toc = find_directives(&chapter.content).any(|directive_match| {
+            matches!(directive_match.directive, Some(Ok(Directive::Toc(_))))
+        });
+        if has_toc {
+            return;
+        }
+
+        let content = &mut chapter.content;
+        let content_len = content.trim_end().len();
+        content.truncate(content_len);
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str("{{#diataxis table-of-contents}}");
+    });
+}
+
+impl AutoCompass {
+    /// Adds a compass directive to each selected chapter which does not already have a compass,
+    /// returning whether any were added.
+    ///
+    /// Generated chapters, which have no source file, are selected by their paths.
+    pub(crate) fn insert_into(&self, book: &mut Book) -> bool {
+        let mut inserted = false;
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let Some(path) = chapter.source_path.as_ref().or(chapter.path.as_ref()) else {
+                return;
+            };
+            if !self.pages.is_match(path) {
+                return;
+            }
+            let has_compass = find_directives(&chapter.content).any(|directive_match| {
+                matches!(directive_match.directive, Some(Ok(Directive::Compass(_))))
+            });
+            if has_compass {
+                return;
+            }
+
+            let content = &mut chapter.content;
+            match self.position {
+                AutoCompassPosition::Top => {
+                    let mut heading_end = None;
+                    let mut offset = 0;
+                    for line in content.split_inclusive('\n') {
+                        let text = line.trim_end();
+                        if text.trim_start().is_empty() || is_directive_line(text) {
+                            offset += line.len();
+                            continue;
+                        }
+                        if text.trim_start().starts_with("# ") {
+                            heading_end = Some(offset + text.len());
+                        }
+                        break;
+                    }
+                    match heading_end {
+                        Some(heading_end) => {
+                            content.insert_str(heading_end, "\n\n{{#diataxis compass}}")
+                        }
+                        None => content.insert_str(0, "{{#diataxis compass}}\n\n"),
+                    }
+                }
+                AutoCompassPosition::Bottom => {
+                    if !content.is_empty() && !content.ends_with('\n') {
+                        content.push('\n');
+                    }
+                    content.push_str("\n{{#diataxis compass}}\n");
+                }
+            }
+            inserted = true;
+        });
+        inserted
+    }
+}
+
+/// Returns whether `line` consists only of `{{#diataxis ...}}` directives.
+fn is_directive_line(line: &str) -> bool {
+    let mut rest_start = 0;
+    let mut any_directive = false;
+    for directive_match in find_directives(line) {
+        if directive_match.directive.is_none()
+            || !line[rest_start..directive_match.start].trim().is_empty()
+        {
+            return false;
+        }
+        rest_start = directive_match.start + directive_match.src.len();
+        any_directive = true;
+    }
+    any_directive && line[rest_start..].trim().is_empty()
+}
+
+impl Config<'_> {
+    /// Adds a generated landing page for each quadrant in the compass whose link points to a page
+    /// which is not in the book.
+    ///
+    /// Each landing page is placed after the part title with the quadrant's title, if there is one,
+    /// and otherwise at the end of the book. It lists the pages of the quadrant's kind and the
+    /// pages in that part.
+    pub(crate) fn insert_landing_pages(
+        &self,
+        book: &mut Book,
+        index: &mut BookIndex,
+        format: Option<OutputFormat>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        use std::fmt::Write;
+
+        let escape = |text: &str| match format {
+            Some(OutputFormat::Html) => escape_html(text),
+            _ => text.to_owned(),
+        };
+        for card in self.compass_cards() {
+            let CompassCard::Quadrant(quadrant) = card else {
+                continue;
+            };
+            let Link::Internal { path, .. } = self.card_link(card).rendered() else {
+                continue;
+            };
+            if path.as_os_str().is_empty() || index.has_page(&path) {
+                continue;
+            }
+            let page_path = normalize(&path).with_extension("md");
+
+            let title = self.card_title(card);
+            let description = self.card_description(card);
+            let mut content = format!("# {}\n\n", escape_markdown(&escape(title)));
+            if !description.is_empty() {
+                if self.markdown_descriptions {
+                    content.push_str(description);
+                } else {
+                    content.push_str(&escape_markdown(&escape(description)));
+                }
+                content.push_str("\n\n");
+            }
+            let with_links = matches!(
+                format,
+                Some(OutputFormat::Html) | Some(OutputFormat::Markdown)
+            );
+            let mut listed_any = false;
+            let mut in_part = false;
+            for item in book.iter() {
+                let chapter = match item {
+                    BookItem::Chapter(chapter) => chapter,
+                    BookItem::PartTitle(part) => {
+                        in_part = part == title;
+                        continue;
+                    }
+                    BookItem::Separator => continue,
+                };
+                if !in_part && index.kind(chapter) != Some(quadrant) {
+                    continue;
+                }
+                listed_any = true;
+                let link = chapter
+                    .path
+                    .as_deref()
+                    .filter(|_| with_links)
+                    .map(|chapter_path| Link::to_chapter(chapter_path).href(Some(&page_path)));
+                let name = escape_markdown(&escape(&chapter.name));
+                match link {
+                    Some(link) => writeln!(content, "- [{name}]({link})"),
+                    None => writeln!(content, "- {name}"),
+                }
+                .expect("internal error: cannot to write to string");
+            }
+            if !listed_any {
+                diagnostics.push(
+                    Diagnostic::new(&page_path, "generated landing page lists no pages").with_help(
+                        format!(
+                            "declare the kind of its pages with `{{{{#diataxis kind {}}}}}` or \
+                             place them after a `# {title}` part title in SUMMARY.md",
+                            quadrant.name()
+                        ),
+                    ),
+                );
+            }
+
+            let chapter = Chapter {
+                name: title.to_owned(),
+                content,
+                path: Some(page_path),
+                ..Chapter::default()
+            };
+            let position = book
+                .sections
+                .iter()
+                .position(|item| matches!(item, BookItem::PartTitle(part) if part == title))
+                .map_or(book.sections.len(), |part_index| part_index + 1);
+            book.sections.insert(position, BookItem::Chapter(chapter));
+            index.pages.insert(normalize(&path));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use googletest::expect_that;
+    use googletest::matchers::{
+        all, contains_substring, elements_are, eq, err, none, not, some, starts_with,
+    };
+    use indoc::indoc;
+    use insta::assert_snapshot;
+    use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+
+    use crate::DiataxisPreprocessor;
+
+    mod auto_toc {
+        use super::*;
+
+        #[googletest::test]
+        fn inserted() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-toc": true
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Guides",
+                            "content": "# Guides\n\n## Overview\n",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "First guide",
+                                    "content": "# First guide",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "guides/first.md",
+                                    "source_path": "guides/first.md",
+                                    "parent_names": ["Guides"]
+                                }
+                            }],
+                            "path": "guides/README.md",
+                            "source_path": "guides/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Reference",
+                            "content": "# Reference\n\nSome words.",
+                            "number": [2],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "API",
+                                    "content": "",
+                                    "number": [2, 1],
+                                    "sub_items": [],
+                                    "path": "reference/api.md",
+                                    "source_path": "reference/api.md",
+                                    "parent_names": ["Reference"]
+                                }
+                            }],
+                            "path": "reference/README.md",
+                            "source_path": "reference/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "{{#diataxis kind tutorial}}\n# Tutorials\n",
+                            "number": [3],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "First steps",
+                                    "content": "# First steps",
+                                    "number": [3, 1],
+                                    "sub_items": [],
+                                    "path": "tutorials/first-steps.md",
+                                    "source_path": "tutorials/first-steps.md",
+                                    "parent_names": ["Tutorials"]
+                                }
+                            }],
+                            "path": "tutorials/README.md",
+                            "source_path": "tutorials/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Blank",
+                            "content": "",
+                            "number": [4],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Leaf",
+                                    "content": "# Leaf",
+                                    "number": [4, 1],
+                                    "sub_items": [],
+                                    "path": "blank/leaf.md",
+                                    "source_path": "blank/leaf.md",
+                                    "parent_names": ["Blank"]
+                                }
+                            }],
+                            "path": "blank/README.md",
+                            "source_path": "blank/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let contents = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter.content.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            expect_that!(
+                contents,
+                elements_are![
+                    eq("# Guides\n\n## Overview\n\n- [First guide](first.html)\n"),
+                    eq("# First guide"),
+                    eq("# Reference\n\nSome words."),
+                    eq(""),
+                    eq(
+                        r#"<span class="diataxis-badge diataxis-badge-tutorial">Tutorial</span>
+# Tutorials
+
+- [First steps](first-steps.html)
+"#
+                    ),
+                    eq("# First steps"),
+                    eq("- [Leaf](leaf.html)\n"),
+                    eq("# Leaf"),
+                ]
+            );
+        }
+        #[googletest::test]
+        fn explicit_toc_kept() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-toc": true
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Sec",
+                            "content": "# Sec\n\n{{#diataxis table-of-contents}}",
+                            "number": [1],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Child",
+                                    "content": "# Child",
+                                    "number": [1, 1],
+                                    "sub_items": [],
+                                    "path": "sec/child.md",
+                                    "source_path": "sec/child.md",
+                                    "parent_names": ["Sec"]
+                                }
+                            }],
+                            "path": "sec/README.md",
+                            "source_path": "sec/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(chapter.content, eq("# Sec\n\n- [Child](child.html)\n"));
+        }
+    }
+
+    mod auto_compass {
+        use super::*;
+
+        #[googletest::test]
+        fn top() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-compass": {
+                                    "pages": ["README.md", "*/index.md"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n\nWelcome!",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "README.md",
+                            "source_path": "README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Guides",
+                            "content": "Some guides.\n\n{{#diataxis compass}}",
+                            "number": [2],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "Deeply nested",
+                                    "content": "# Deeply nested",
+                                    "number": [2, 1],
+                                    "sub_items": [],
+                                    "path": "guides/nested/index.md",
+                                    "source_path": "guides/nested/index.md",
+                                    "parent_names": ["Guides"]
+                                }
+                            }],
+                            "path": "guides/index.md",
+                            "source_path": "guides/index.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "Reference",
+                            "content": "Reference material.",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "reference/index.md",
+                            "source_path": "reference/index.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let contents = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter.content.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            expect_that!(
+                contents,
+                elements_are![
+                    starts_with("# Introduction\n\n- **[Tutorials]"),
+                    all![
+                        starts_with("Some guides.\n\n- **[Tutorials]"),
+                        not(contains_substring("{{#diataxis")),
+                    ],
+                    eq("# Deeply nested"),
+                    starts_with("- **[Tutorials]"),
+                ]
+            );
+            assert_snapshot!(contents[0]);
+        }
+
+        #[googletest::test]
+        fn top_after_kind() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-compass": {
+                                    "pages": ["guides.md"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Guides",
+                            "content": "\n{{#diataxis kind how-to-guide}}\n# Guides\n\nSome guides.",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "guides.md",
+                            "source_path": "guides.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                starts_with(
+                    "\n**How-to guide**\n# Guides\n\n- **[Tutorials](tutorials/index.md)**"
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn bottom() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-compass": {
+                                    "pages": ["**/README.md"],
+                                    "position": "bottom"
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Guides",
+                            "content": "# Guides\n\nSome guides.",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "guides/README.md",
+                            "source_path": "guides/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let chapter = match &book.sections[0] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected first item"),
+            };
+            expect_that!(
+                chapter.content,
+                all![
+                    starts_with(
+                        "# Guides\n\nSome guides.\n\n- **[Tutorials](../tutorials/index.md)**"
+                    ),
+                    contains_substring("- **[Reference](../reference-materials/index.md)**"),
+                ]
+            );
+        }
+
+        #[googletest::test]
+        fn invalid_position() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "auto-compass": {
+                                    "pages": ["README.md"],
+                                    "position": "middle"
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let result = DiataxisPreprocessor::new().run(&ctx, book);
+            expect_that!(
+                result.map_err(|err| format!("{err:#}")),
+                err(eq(
+                    "cannot parse `auto-compass` field: `position` field must be either \"top\" or \"bottom\""
+                ))
+            );
+        }
+    }
+
+    mod landing_pages {
+        use super::*;
+
+        #[googletest::test]
+        fn generated() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "landing-pages": true,
+                                "compass": {
+                                    "order": ["tutorials", "reference"],
+                                    "reference": {
+                                        "description": "Facts & figures"
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "# Tutorials\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "tutorials/README.md",
+                            "source_path": "tutorials/README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "PartTitle": "Reference"
+                    }, {
+                        "Chapter": {
+                            "name": "API",
+                            "content": "{{#diataxis kind reference}}\n# API",
+                            "number": [2],
+                            "sub_items": [{
+                                "Chapter": {
+                                    "name": "CLI",
+                                    "content": "{{#diataxis kind reference}}\n# CLI",
+                                    "number": [2, 1],
+                                    "sub_items": [],
+                                    "path": "api/cli.md",
+                                    "source_path": "api/cli.md",
+                                    "parent_names": ["API"]
+                                }
+                            }],
+                            "path": "api/README.md",
+                            "source_path": "api/README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let names = book
+                .sections
+                .iter()
+                .map(|item| match item {
+                    BookItem::Chapter(chapter) => chapter.name.clone(),
+                    BookItem::PartTitle(title) => format!("part: {title}"),
+                    BookItem::Separator => "separator".to_owned(),
+                })
+                .collect::<Vec<_>>();
+            expect_that!(
+                names,
+                elements_are![
+                    eq("Tutorials"),
+                    eq("part: Reference"),
+                    eq("Reference"),
+                    eq("API"),
+                ]
+            );
+            let landing_page = match &book.sections[2] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected third item"),
+            };
+            expect_that!(
+                landing_page.path,
+                some(eq(Path::new("reference-materials/index.md")))
+            );
+            expect_that!(landing_page.source_path, none());
+            expect_that!(
+                landing_page.content,
+                all![
+                    starts_with("# Reference\n\nFacts &amp; figures\n\n"),
+                    contains_substring("- [API](../api/index.md)"),
+                    contains_substring("- [CLI](../api/cli.md)"),
+                ]
+            );
+            assert_snapshot!(landing_page.content);
+        }
+
+        #[googletest::test]
+        fn with_auto_compass() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "landing-pages": true,
+                                "auto-compass": {
+                                    "pages": ["*/index.md"]
+                                },
+                                "compass": {
+                                    "order": ["tutorials", "reference"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Tutorials",
+                            "content": "# Tutorials",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "tutorials/index.md",
+                            "source_path": "tutorials/index.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let landing_page = match &book.sections[1] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected second item"),
+            };
+            expect_that!(
+                landing_page.content,
+                all![
+                    starts_with("# Reference\n\n<span>"),
+                    contains_substring(r#"<a href="../tutorials/index.html">"#),
+                    contains_substring(
+                        r#"<blockquote class="diataxis-card-active" aria-current="true">"#
+                    ),
+                    contains_substring(r#"<a href="index.html">Reference</a>"#),
+                    not(contains_substring("{{#diataxis")),
+                ]
+            );
+        }
+
+        #[googletest::test]
+        fn escaped_chapter_names() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "landing-pages": true,
+                                "compass": {
+                                    "order": ["reference"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "markdown",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "[Draft] *new* API",
+                            "content": "{{#diataxis kind reference}}\n# API",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "api.md",
+                            "source_path": "api.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let landing_page = match &book.sections[1] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected second item"),
+            };
+            expect_that!(
+                landing_page.content,
+                contains_substring(r"- [\[Draft\] \*new\* API](../api.md)")
+            );
+        }
+
+        #[googletest::test]
+        fn lists_part_pages() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "landing-pages": true,
+                                "compass": {
+                                    "order": ["reference"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "README.md",
+                            "source_path": "README.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "PartTitle": "Reference"
+                    }, {
+                        "Chapter": {
+                            "name": "API",
+                            "content": "# API",
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "api.md",
+                            "source_path": "api.md",
+                            "parent_names": []
+                        }
+                    }, {
+                        "Chapter": {
+                            "name": "CLI",
+                            "content": "# CLI",
+                            "number": [3],
+                            "sub_items": [],
+                            "path": "cli.md",
+                            "source_path": "cli.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            let landing_page = match &book.sections[2] {
+                BookItem::Chapter(chapter) => chapter,
+                _ => panic!("unexpected third item"),
+            };
+            expect_that!(
+                landing_page.content,
+                eq(indoc! {"
+                    # Reference
+
+                    Technical information
+
+                    - [API](../api.md)
+                    - [CLI](../cli.md)
+                "})
+            );
+        }
+
+        #[googletest::test]
+        fn empty_list_reported() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {
+                                "strict": true,
+                                "landing-pages": true,
+                                "compass": {
+                                    "order": ["reference"]
+                                }
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "README.md",
+                            "source_path": "README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let err = DiataxisPreprocessor::new().run(&ctx, book).unwrap_err();
+            expect_that!(
+                err.to_string(),
+                contains_substring(
+                    "reference-materials/index.md: generated landing page lists no pages"
+                )
+            );
+        }
+
+        #[googletest::test]
+        fn disabled_by_default() {
+            let input_json = indoc! {br##"
+                [{
+                    "root": "/path/to/book",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "diataxis": {}
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                }, {
+                    "sections": [{
+                        "Chapter": {
+                            "name": "Introduction",
+                            "content": "# Introduction\n{{#diataxis compass}}",
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "README.md",
+                            "source_path": "README.md",
+                            "parent_names": []
+                        }
+                    }],
+                    "__non_exhaustive": null
+                }]
+            "##};
+            let (ctx, book) = CmdPreprocessor::parse_input(&input_json[..]).unwrap();
+            let book = DiataxisPreprocessor::new().run(&ctx, book).unwrap();
+            expect_that!(book.sections.len(), eq(1));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use handlebars::Handlebars;
+use serde_json::Value;
+
+/// User-supplied handlebars templates which replace the built-in markup of directives.
+#[derive(Debug, Default)]
+pub(crate) struct Templates {
+    registry: Handlebars<'static>,
+}
+
+impl Templates {
+    /// The names of the directives whose markup can be templated.
+    pub(crate) const NAMES: [&str; 2] = ["compass", "toc"];
+
+    /// Loads the template files at the given paths, which are relative to the book's root
+    /// directory.
+    pub(crate) fn load<'a>(
+        root: &Path,
+        paths: impl IntoIterator<Item = (&'static str, &'a str)>,
+    ) -> Result<Self> {
+        let mut registry = Handlebars::new();
+        for (name, path) in paths {
+            let path = root.join(path);
+            registry
+                .register_template_file(name, &path)
+                .with_context(|| format!("cannot load `{name}` template {}", path.display()))?;
+        }
+        Ok(Self { registry })
+    }
+
+    /// Renders the template called `name` with `data`, if the user has supplied it.
+    pub(crate) fn render(&self, name: &str, data: &Value) -> Option<Result<String>> {
+        if !self.registry.has_template(name) {
+            return None;
+        }
+        let rendered = self
+            .registry
+            .render(name, data)
+            .with_context(|| format!("cannot render `{name}` template"));
+        Some(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{eq, none, ok, some};
+    use serde_json::json;
+
+    #[googletest::test]
+    fn render() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join("compass.hbs"),
+            "{{#each sections}}<a href=\"{{link}}\">{{title}}</a>{{/each}}",
+        )
+        .unwrap();
+        let templates = Templates::load(root.path(), [("compass", "compass.hbs")]).unwrap();
+
+        let data = json!({
+            "sections": [{ "title": "Tips & tricks", "link": "tips.html" }],
+        });
+        expect_that!(
+            templates.render("compass", &data),
+            some(ok(eq("<a href=\"tips.html\">Tips &amp; tricks</a>")))
+        );
+        expect_that!(templates.render("toc", &data), none());
+    }
+}
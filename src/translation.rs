@@ -0,0 +1,116 @@
+use crate::quadrant::Quadrant;
+
+/// The default text of one section of the compass.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct SectionText {
+    pub(crate) title: &'static str,
+    pub(crate) description: &'static str,
+}
+
+/// Returns the built-in text of `quadrant`'s compass section in `language`, if it has been
+/// translated.
+///
+/// Languages are matched by their primary subtag, so `fr-CA` uses the `fr` translation.
+pub(crate) fn builtin(language: &str, quadrant: Quadrant) -> Option<SectionText> {
+    let text = |title, description| Some(SectionText { title, description });
+    match (primary_subtag(language), quadrant) {
+        ("en", Quadrant::Tutorial) => text("Tutorials", "Hands-on lessons"),
+        ("en", Quadrant::HowToGuide) => text(
+            "How-to guides",
+            "Step-by-step instructions for common tasks",
+        ),
+        ("en", Quadrant::Explanation) => text("Explanation", "Long-form discussion of key topics"),
+        ("en", Quadrant::Reference) => text("Reference", "Technical information"),
+        ("de", Quadrant::Tutorial) => text("Tutorials", "Praktische Lektionen"),
+        ("de", Quadrant::HowToGuide) => text(
+            "Anleitungen",
+            "Schritt-für-Schritt-Anleitungen für häufige Aufgaben",
+        ),
+        ("de", Quadrant::Explanation) => {
+            text("Erklärung", "Ausführliche Erörterung zentraler Themen")
+        }
+        ("de", Quadrant::Reference) => text("Referenz", "Technische Informationen"),
+        ("fr", Quadrant::Tutorial) => text("Tutoriels", "Leçons pratiques"),
+        ("fr", Quadrant::HowToGuide) => text(
+            "Guides pratiques",
+            "Instructions pas à pas pour les tâches courantes",
+        ),
+        ("fr", Quadrant::Explanation) => {
+            text("Explication", "Discussion approfondie des sujets clés")
+        }
+        ("fr", Quadrant::Reference) => text("Référence", "Informations techniques"),
+        ("ja", Quadrant::Tutorial) => text("チュートリアル", "実践的なレッスン"),
+        ("ja", Quadrant::HowToGuide) => text("ハウツーガイド", "よくある作業の手順"),
+        ("ja", Quadrant::Explanation) => text("解説", "主要なトピックの詳しい説明"),
+        ("ja", Quadrant::Reference) => text("リファレンス", "技術情報"),
+        _ => None,
+    }
+}
+
+/// The labels of the axes of the Diátaxis map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AxisLabels {
+    /// The top of the map, where tutorials and how-to guides are.
+    pub(crate) action: &'static str,
+
+    /// The bottom of the map, where explanation and reference are.
+    pub(crate) cognition: &'static str,
+
+    /// The left of the map, where tutorials and explanation are.
+    pub(crate) acquisition: &'static str,
+
+    /// The right of the map, where how-to guides and reference are.
+    pub(crate) application: &'static str,
+}
+
+/// Returns the built-in labels of the axes of the compass in `language`, if they have been
+/// translated.
+pub(crate) fn builtin_axis_labels(language: &str) -> Option<AxisLabels> {
+    let labels = |action, cognition, acquisition, application| {
+        Some(AxisLabels {
+            action,
+            cognition,
+            acquisition,
+            application,
+        })
+    };
+    match primary_subtag(language) {
+        "en" => labels("Action", "Cognition", "Acquisition", "Application"),
+        "de" => labels("Handeln", "Erkenntnis", "Aneignung", "Anwendung"),
+        "fr" => labels("Action", "Cognition", "Acquisition", "Application"),
+        "ja" => labels("実践", "認識", "習得", "応用"),
+        _ => None,
+    }
+}
+
+/// Returns the primary subtag of a language tag, such as `fr` for `fr-CA`.
+pub(crate) fn primary_subtag(language: &str) -> &str {
+    language.split(['-', '_']).next().unwrap_or(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use googletest::expect_that;
+    use googletest::matchers::{eq, none, some};
+
+    #[googletest::test]
+    fn complete() {
+        for language in ["en", "de", "fr", "ja"] {
+            for quadrant in Quadrant::ALL {
+                expect_that!(builtin(language, quadrant).is_some(), eq(true));
+            }
+            expect_that!(builtin_axis_labels(language).is_some(), eq(true));
+        }
+    }
+
+    #[googletest::test]
+    fn subtags() {
+        expect_that!(
+            builtin("fr-CA", Quadrant::Reference).map(|text| text.title),
+            some(eq("Référence"))
+        );
+        expect_that!(builtin("eo", Quadrant::Reference), none());
+    }
+}